@@ -0,0 +1,74 @@
+use crate::{get_logs_in_batches, serialize_logs_with};
+use ethers::abi::{Abi, Event, RawLog, Token};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, Filter, Log, H256, I256, U64};
+use ethers::utils::hex;
+use futures::lock::Mutex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Events of `abi` named in `event_names`, every overload included; every non-anonymous event when empty.
+// Anonymous events have no signature topic to filter and match on, so they are not supported.
+pub fn select_events(abi: &Abi, event_names: &[String]) -> Result<Vec<Event>, String> {
+    if event_names.is_empty() {
+        return Ok(abi.events().filter(|event| !event.anonymous).cloned().collect());
+    }
+    let mut events = Vec::new();
+    for event_name in event_names {
+        let overloads = abi.events_by_name(event_name).map_err(|_| format!("Unknown event: {}", event_name))?;
+        if overloads.iter().any(|event| event.anonymous) {
+            return Err(format!("Anonymous event {} can not be fetched", event_name));
+        }
+        events.extend(overloads.iter().cloned());
+    }
+    Ok(events)
+}
+
+// Uints and ints are serialized like U256 and I256 in the pool events; tuples become lists as tokens carry no component names
+pub fn token_to_json(token: &Token) -> Value {
+    match token {
+        Token::Address(address) => serde_json::json!(address),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => Value::String(format!("0x{}", hex::encode(bytes))),
+        Token::Int(value) => serde_json::json!(I256::from_raw(*value)),
+        Token::Uint(value) => serde_json::json!(value),
+        Token::Bool(value) => Value::Bool(*value),
+        Token::String(value) => Value::String(value.clone()),
+        Token::FixedArray(tokens) | Token::Array(tokens) | Token::Tuple(tokens) => Value::Array(tokens.iter().map(token_to_json).collect()),
+    }
+}
+
+// Decode `log` with the event matching its signature into `{ "type": event name, "data": { parameter name: value } }`
+pub fn decode_abi_event(events: &HashMap<H256, Event>, log: &Log) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let topic0 = log.topics.first().ok_or("Missing event signature")?;
+    let event = events.get(topic0).ok_or_else(|| format!("Unknown event signature: {:?}", topic0))?;
+    let parsed = event.parse_log(RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    })?;
+    let mut data = Map::new();
+    for (index, param) in parsed.params.iter().enumerate() {
+        // Unnamed parameters are keyed by their position
+        let name = if param.name.is_empty() { index.to_string() } else { param.name.clone() };
+        data.insert(name, token_to_json(&param.value));
+    }
+    Ok(serde_json::json!({ "type": event.name, "data": data }))
+}
+
+pub async fn get_events(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    addresses: Vec<Address>,
+    events: Vec<Event>,
+    from_block: U64,
+    to_block: U64,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let events: HashMap<H256, Event> = events.into_iter().map(|event| (event.signature(), event)).collect();
+    let filter = Filter::new()
+        .address(addresses)
+        .topic0(events.keys().cloned().collect::<Vec<H256>>());
+    let mut logs = get_logs_in_batches(provider.clone(), filter, from_block, to_block, max_concurrent_batches).await?;
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    serialize_logs_with(logs, provider, block_cache, |log| decode_abi_event(&events, log)).await
+}
@@ -0,0 +1,166 @@
+use crate::price_math::{u256_to_biguint, Price};
+use crate::{
+    decode_swap, get_block_number_range, get_block_timestamp, get_pool_abi, get_pool_info, get_previous_sqrt_price,
+    get_swap_logs, get_token_abis, get_token_info, interval_end_timestamp,
+};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, I256, U256};
+use futures::lock::Mutex;
+use num_bigint::BigUint;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// A swap reduced to what a candle needs
+#[derive(Debug, Clone)]
+pub struct CandleSwap {
+    pub timestamp: u64,
+    pub sqrt_price_x96: U256,
+    pub amount0: I256,
+    pub amount1: I256,
+}
+
+// Swaps of one interval. Prices are compared as sqrt prices, which order the same way.
+struct Candle {
+    open: U256,
+    high: U256,
+    low: U256,
+    close: U256,
+    swap_count: u64,
+    volume0: U256,
+    volume1: U256,
+}
+
+impl Candle {
+    fn new(sqrt_price_x96: U256) -> Self {
+        Candle {
+            open: sqrt_price_x96,
+            high: sqrt_price_x96,
+            low: sqrt_price_x96,
+            close: sqrt_price_x96,
+            swap_count: 0,
+            volume0: U256::zero(),
+            volume1: U256::zero(),
+        }
+    }
+
+    fn add_swap(&mut self, swap: &CandleSwap) {
+        self.high = self.high.max(swap.sqrt_price_x96);
+        self.low = self.low.min(swap.sqrt_price_x96);
+        self.close = swap.sqrt_price_x96;
+        self.swap_count += 1;
+        self.volume0 += swap.amount0.unsigned_abs();
+        self.volume1 += swap.amount1.unsigned_abs();
+    }
+}
+
+fn price_string(sqrt_price_x96: Option<U256>, token0_decimals: u32, token1_decimals: u32) -> Option<String> {
+    sqrt_price_x96.map(|sqrt_price_x96| {
+        Price::from_sqrt_price_x96(sqrt_price_x96).adjust_decimals(token0_decimals, token1_decimals).to_decimal_string()
+    })
+}
+
+fn volume_string(volume: U256, decimals: u32) -> String {
+    Price::new(u256_to_biguint(volume), BigUint::from(10u8).pow(decimals)).to_decimal_string()
+}
+
+// Aggregate `swaps`, ordered by time, into the candles of every interval between the two timestamps. Candles are keyed
+// like get_pool_price_ratios by the timestamp closing their interval. The open is the price after the first swap of
+// the interval and intervals without swaps repeat the previous close, starting from `previous_sqrt_price`. The last
+// candle is the one containing `end_timestamp`, even when its interval closes after it.
+// Prices are of token0 in token1 and, like the volumes, adjusted for the token decimals, and null until one is known.
+pub fn build_candles(
+    swaps: &[CandleSwap],
+    start_timestamp: u64,
+    end_timestamp: u64,
+    interval: u64,
+    previous_sqrt_price: Option<U256>,
+    token0_decimals: u32,
+    token1_decimals: u32,
+) -> Vec<Value> {
+    let mut candles: HashMap<u64, Candle> = HashMap::new();
+    let mut previous_sqrt_price = previous_sqrt_price;
+    for swap in swaps {
+        // The block range is only an estimate of the time range, earlier swaps just set the price to start from
+        if swap.timestamp < start_timestamp {
+            previous_sqrt_price = Some(swap.sqrt_price_x96);
+            continue;
+        }
+        if swap.timestamp > end_timestamp {
+            break;
+        }
+        candles.entry(interval_end_timestamp(swap.timestamp, interval))
+            .or_insert_with(|| Candle::new(swap.sqrt_price_x96))
+            .add_swap(swap);
+    }
+
+    let mut result = Vec::new();
+    let mut timestamp = interval_end_timestamp(start_timestamp, interval);
+    let last_timestamp = interval_end_timestamp(end_timestamp, interval);
+    while timestamp <= last_timestamp {
+        // None until a price is known
+        let candle = candles.remove(&timestamp).or_else(|| previous_sqrt_price.map(Candle::new));
+        let price = |sqrt_price_x96: Option<U256>| price_string(sqrt_price_x96, token0_decimals, token1_decimals);
+        let (swap_count, volume0, volume1) = candle.as_ref()
+            .map(|candle| (candle.swap_count, candle.volume0, candle.volume1))
+            .unwrap_or_default();
+        // Volume weighted average price of token0 in token1, the token1 volume per token0 volume
+        let vwap = (!volume0.is_zero()).then(|| {
+            Price::new(u256_to_biguint(volume1), u256_to_biguint(volume0))
+                .adjust_decimals(token0_decimals, token1_decimals)
+                .to_decimal_string()
+        });
+        result.push(serde_json::json!({
+            "timestamp": timestamp,
+            "open": price(candle.as_ref().map(|candle| candle.open)),
+            "high": price(candle.as_ref().map(|candle| candle.high)),
+            "low": price(candle.as_ref().map(|candle| candle.low)),
+            "close": price(candle.as_ref().map(|candle| candle.close)),
+            "swap_count": swap_count,
+            "volume0": volume_string(volume0, token0_decimals),
+            "volume1": volume_string(volume1, token1_decimals),
+            "vwap": vwap,
+        }));
+        if let Some(candle) = candle {
+            previous_sqrt_price = Some(candle.close);
+        }
+        timestamp += interval;
+    }
+    result
+}
+
+pub async fn get_pool_candles(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    pool_address: Address,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    interval: u64,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>> {
+    let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
+
+    let token_abis = get_token_abis();
+    let (token0, token1, _, _) = get_pool_info(provider.clone(), pool_address, get_pool_abi()).await?;
+    let (_, _, token0_decimals) = get_token_info(provider.clone(), token0, token_abis.clone()).await?;
+    let (_, _, token1_decimals) = get_token_info(provider.clone(), token1, token_abis).await?;
+
+    let logs = get_swap_logs(provider.clone(), pool_address, start_block_number, end_block_number, max_concurrent_batches).await?;
+    let mut swaps = Vec::new();
+    for log in &logs {
+        let timestamp = get_block_timestamp(provider.clone(), block_cache.clone(), log.block_number.ok_or("Missing block number")?.as_u64()).await?;
+        let (sqrt_price_x96, amount0, amount1) = decode_swap(log)?;
+        swaps.push(CandleSwap { timestamp, sqrt_price_x96, amount0, amount1 });
+    }
+    let previous_sqrt_price = get_previous_sqrt_price(provider.clone(), pool_address, start_block_number, max_concurrent_batches).await?;
+
+    Ok(build_candles(
+        &swaps,
+        start_timestamp,
+        end_timestamp,
+        interval,
+        previous_sqrt_price,
+        token0_decimals.as_u64().unwrap() as u32,
+        token1_decimals.as_u64().unwrap() as u32,
+    ))
+}
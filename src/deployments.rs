@@ -0,0 +1,143 @@
+use ethers::types::{Address, H256};
+use std::str::FromStr;
+
+// Protocols sharing the Uniswap V3 pool events, with the fee tiers their factories enable: (fee, tick spacing)
+const PROTOCOLS: [(&str, &[(u32, i32)]); 3] = [
+    ("uniswap_v3", &[(100, 1), (500, 10), (3000, 60), (10000, 200)]),
+    ("sushiswap_v3", &[(100, 1), (500, 10), (3000, 60), (10000, 200)]),
+    ("pancakeswap_v3", &[(100, 1), (500, 10), (2500, 50), (10000, 200)]),
+];
+
+// Factory deployments: (protocol, chain id, factory address, pool deployer address, NonfungiblePositionManager address, pool init code hash, factory deployment block).
+// Pools are deployed with CREATE2 by the factory itself, except on PancakeSwap V3 which uses a separate pool deployer.
+const DEPLOYMENTS: [(&str, u64, &str, &str, &str, &str, u64); 8] = [
+    ("uniswap_v3", 1, "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0xC36442b4a4522E871399CD717aBCa847E3E3Aa8E", "e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54", 12369621), // Ethereum
+    ("uniswap_v3", 10, "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0xC36442b4a4522E871399CD717aBCa847E3E3Aa8E", "e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54", 0), // Optimism
+    ("uniswap_v3", 137, "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0xC36442b4a4522E871399CD717aBCa847E3E3Aa8E", "e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54", 22757547), // Polygon
+    ("uniswap_v3", 8453, "0x33128a8fC17869897dcE68Ed026d694621f6FDfD", "0x33128a8fC17869897dcE68Ed026d694621f6FDfD", "0x03a520b32C04BF3bEEf7BEb72E919cf822Ed34f1", "e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54", 1371680), // Base
+    ("uniswap_v3", 42161, "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0x1F98431c8aD98523631AE4a59f267346ea31F984", "0xC36442b4a4522E871399CD717aBCa847E3E3Aa8E", "e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54", 165), // Arbitrum One
+    ("sushiswap_v3", 1, "0xbACEB8eC6b9355Dfc0269C18bac9d6E2Bdc29C4F", "0xbACEB8eC6b9355Dfc0269C18bac9d6E2Bdc29C4F", "0x2214A42d8e2A1d20635c2cb0664422c528B6A432", "e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54", 16955547), // Ethereum
+    ("pancakeswap_v3", 1, "0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865", "0x41ff9AA7e16B8B1a8a8dc4f0eFacd93D02d071c9", "0x46A15B0b27311cedF172AB29E4f4766fbE7F4364", "6ce8eb472fa82df5469c6ab6d485f17c3ad13c8cd7af59b3d4a8026c5ce0f7e2", 16950686), // Ethereum
+    ("pancakeswap_v3", 56, "0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865", "0x41ff9AA7e16B8B1a8a8dc4f0eFacd93D02d071c9", "0x46A15B0b27311cedF172AB29E4f4766fbE7F4364", "6ce8eb472fa82df5469c6ab6d485f17c3ad13c8cd7af59b3d4a8026c5ce0f7e2", 26956207), // BNB Chain
+];
+
+// Chain whose deployments are used on local development chains
+const FALLBACK_CHAIN_ID: u64 = 1;
+// Chain ids of local development nodes (anvil and hardhat use 31337, ganache and geth --dev 1337), taken for mainnet forks
+const DEV_CHAIN_IDS: [u64; 2] = [31337, 1337];
+
+// Uniswap V2 factory deployments: (chain id, factory address, pair init code hash, factory deployment block)
+const V2_DEPLOYMENTS: [(u64, &str, &str, u64); 1] = [
+    (1, "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f", "96e8ac4277198ff8b6f785478aa9a39f403cb768dd02cbee326c3e7da348845f", 10000835), // Ethereum
+];
+
+// Uniswap V4 PoolManager deployments: (chain id, pool manager address, deployment block)
+const V4_DEPLOYMENTS: [(u64, &str, u64); 1] = [
+    (1, "0x000000000004444c5dc75cB358380D2e3dE08A90", 21688329), // Ethereum
+];
+
+#[derive(Debug, Clone)]
+pub struct Deployment {
+    pub protocol: &'static str,
+    pub chain_id: u64,
+    pub factory_address: Address,
+    pub pool_deployer: Address,
+    pub position_manager: Address,
+    pub init_code_hash: H256,
+    pub deployment_block: u64,
+    pub fee_tiers: &'static [(u32, i32)],
+}
+
+pub fn get_deployment(protocol: &str, chain_id: u64) -> Option<Deployment> {
+    let fee_tiers = PROTOCOLS.iter().find(|profile| profile.0 == protocol)?.1;
+    DEPLOYMENTS.iter()
+        .find(|deployment| deployment.0 == protocol && deployment.1 == chain_id)
+        .map(|(protocol, chain_id, factory_address, pool_deployer, position_manager, init_code_hash, deployment_block)| Deployment {
+            protocol,
+            chain_id: *chain_id,
+            factory_address: Address::from_str(factory_address).unwrap(),
+            pool_deployer: Address::from_str(pool_deployer).unwrap(),
+            position_manager: Address::from_str(position_manager).unwrap(),
+            init_code_hash: H256::from_str(init_code_hash).unwrap(),
+            deployment_block: *deployment_block,
+            fee_tiers,
+        })
+}
+
+pub fn supported_chain_ids(protocol: &str) -> Vec<u64> {
+    DEPLOYMENTS.iter().filter(|deployment| deployment.0 == protocol).map(|deployment| deployment.1).collect()
+}
+
+pub fn supported_protocols() -> Vec<&'static str> {
+    PROTOCOLS.iter().map(|profile| profile.0).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct V2Deployment {
+    pub factory_address: Address,
+    pub init_code_hash: H256,
+    pub deployment_block: u64,
+}
+
+pub fn get_v2_deployment(chain_id: u64) -> Option<V2Deployment> {
+    V2_DEPLOYMENTS.iter()
+        .find(|deployment| deployment.0 == chain_id)
+        .map(|(_, factory_address, init_code_hash, deployment_block)| V2Deployment {
+            factory_address: Address::from_str(factory_address).unwrap(),
+            init_code_hash: H256::from_str(init_code_hash).unwrap(),
+            deployment_block: *deployment_block,
+        })
+}
+
+pub fn supported_v2_chain_ids() -> Vec<u64> {
+    V2_DEPLOYMENTS.iter().map(|deployment| deployment.0).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct V4Deployment {
+    pub pool_manager: Address,
+    pub deployment_block: u64,
+}
+
+pub fn get_v4_deployment(chain_id: u64) -> Option<V4Deployment> {
+    V4_DEPLOYMENTS.iter()
+        .find(|deployment| deployment.0 == chain_id)
+        .map(|(_, pool_manager, deployment_block)| V4Deployment {
+            pool_manager: Address::from_str(pool_manager).unwrap(),
+            deployment_block: *deployment_block,
+        })
+}
+
+pub fn supported_v4_chain_ids() -> Vec<u64> {
+    V4_DEPLOYMENTS.iter().map(|deployment| deployment.0).collect()
+}
+
+// Every deployment a fetcher queries on its chain
+#[derive(Debug, Clone)]
+pub struct Deployments {
+    pub deployment: Deployment,
+    pub v2_deployment: Option<V2Deployment>,
+    pub v4_deployment: Option<V4Deployment>,
+}
+
+// The deployments of `protocol` on `chain_id`. A local development chain, such as an anvil or hardhat fork reporting
+// chain id 31337, is taken for a fork of Ethereum and gets the mainnet addresses. None for an unknown protocol or a
+// chain the protocol is not registered on.
+pub fn resolve_deployments(protocol: &str, chain_id: u64) -> Option<Deployments> {
+    if let Some(deployment) = get_deployment(protocol, chain_id) {
+        return Some(Deployments {
+            deployment,
+            v2_deployment: get_v2_deployment(chain_id),
+            v4_deployment: get_v4_deployment(chain_id),
+        });
+    }
+    if !DEV_CHAIN_IDS.contains(&chain_id) {
+        return None;
+    }
+    let deployment = get_deployment(protocol, FALLBACK_CHAIN_ID)?;
+    Some(Deployments {
+        deployment: Deployment { chain_id, ..deployment },
+        v2_deployment: get_v2_deployment(FALLBACK_CHAIN_ID),
+        v4_deployment: get_v4_deployment(FALLBACK_CHAIN_ID),
+    })
+}
@@ -0,0 +1,77 @@
+use crate::{block_on, serialize_logs, tag_protocol, PyValue, BATCH_SIZE};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Filter, U64};
+use futures::lock::Mutex;
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+// Pool events of a block range, fetched, decoded and yielded one BATCH_SIZE window at a time so that only the
+// current window is held in memory however long the range is. Block timestamps are cached per window for the same
+// reason, instead of in the fetcher's cache.
+#[pyclass]
+pub struct PoolEventStream {
+    provider: Arc<Provider<Http>>,
+    runtime: &'static Runtime,
+    // One filter per topic0 selection, as built by get_pool_event_filters
+    filters: Vec<Filter>,
+    protocol: &'static str,
+    next_block: U64,
+    to_block: U64,
+}
+
+impl PoolEventStream {
+    pub fn new(
+        provider: Arc<Provider<Http>>,
+        runtime: &'static Runtime,
+        filters: Vec<Filter>,
+        protocol: &'static str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Self {
+        PoolEventStream { provider, runtime, filters, protocol, next_block: from_block, to_block }
+    }
+}
+
+// Events of one window in the envelope of get_pool_events_by_pool_addresses, ordered by block number and log index
+async fn fetch_window(
+    provider: Arc<Provider<Http>>,
+    filters: Vec<Filter>,
+    from_block: U64,
+    to_block: U64,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let mut logs = Vec::new();
+    for filter in filters {
+        logs.extend(provider.get_logs(&filter.from_block(from_block).to_block(to_block)).await?);
+    }
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    serialize_logs(logs, provider, Arc::new(Mutex::new(HashMap::new()))).await
+}
+
+#[pymethods]
+impl PoolEventStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    // A window that fails to fetch is not skipped, the next call retries it
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        if self.next_block > self.to_block {
+            return Ok(None);
+        }
+        let from_block = self.next_block;
+        let to_block = min(from_block + BATCH_SIZE, self.to_block);
+        let window = fetch_window(self.provider.clone(), self.filters.clone(), from_block, to_block);
+        let mut batch = match block_on(py, self.runtime, window)? {
+            Ok(batch) => tag_protocol(batch, self.protocol),
+            Err(e) => return Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+        };
+        batch["from_block"] = serde_json::json!(from_block.as_u64());
+        batch["to_block"] = serde_json::json!(to_block.as_u64());
+        self.next_block = to_block + 1;
+        Ok(Some(PyValue(batch).into_py(py)))
+    }
+}
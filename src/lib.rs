@@ -4,7 +4,7 @@ use chrono::Utc;
 use ethers::{abi::Abi, contract::Contract, providers:: { Http, Middleware, Provider}, types::Address};
 use serde::{Deserialize, Serialize};
 use sha2::{ Digest, Sha256};
-use std::{collections::HashSet, sync::Arc};
+use std::{collections::HashSet, sync::{Arc, OnceLock}};
 use serde_json::{self, Number, Value};
 use std::marker::Send;
 use ethers::types::{Filter, Log, H160, H256, U64, I256, U256, Block, BlockNumber};
@@ -13,24 +13,52 @@ use ethers::contract::EthLogDecode;
 use ethers::contract::EthEvent;
 use ethers::utils::hex;
 
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::str::FromStr;
 use pyo3::{IntoPy, PyObject};
 use pyo3::types::{PyList, PyDict};
-use futures::{future::join_all, lock::Mutex};
-
-
-use num_bigint::BigInt;
+use futures::{lock::Mutex, stream, StreamExt, TryStreamExt};
+
+
+mod deployments;
+use deployments::{resolve_deployments, supported_chain_ids, supported_protocols, supported_v2_chain_ids, supported_v4_chain_ids, Deployment, Deployments, V2Deployment, V4Deployment};
+mod abi_events;
+mod candles;
+mod event_stream;
+mod liquidity_replay;
+mod pool_state;
+mod price_math;
+use price_math::{price_strings, Price};
+mod positions;
+mod signals;
+mod v2;
+mod v4;
 
 const BATCH_SIZE: usize = 10000; // Number of blocks to fetch in each batch
 const NUM_BLOCKS: u64 = 100; // Number of blocks to consider for average block time calculation
-const FACTORY_ADDRESS: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+const MAX_CONCURRENT_BATCHES: usize = 4; // Default number of BATCH_SIZE block windows fetched at once
+const SIGNAL_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100); // How often a running fetch checks for Ctrl-C
 const POOL_CREATED_SIGNATURE: &str = "0x783cca1c0412dd0d695e784568c96da2e9c22ff989357a2e8b1d9b2b4e6b7118";
 const SWAP_EVENT_SIGNATURE: &str = "c42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+// PancakeSwap V3 adds the protocol fees to its Swap event
+const PANCAKE_SWAP_EVENT_SIGNATURE: &str = "19b47279256b2a23a1665c810c8d55a1758940ee09377d4f8d26497a3577dc83";
 const MINT_EVENT_SIGNATURE: &str = "7a53080ba414158be7ec69b987b5fb7d07dee101fe85488f0853ae16239d0bde";
 const BURN_EVENT_SIGNATURE: &str = "0c396cd989a39f4459b5fa1aed6a9a8dcdbc45908acfd67e028cd568da98982c";
 const COLLECT_EVENT_SIGNATURE: &str = "70935338e69775456a85ddef226c395fb668b63fa0115f5f20610b388e6ca9c0";
+const FLASH_EVENT_SIGNATURE: &str = "bdbdb71d7860376ba52b25a5028beea23581364a40522f6bcfb86bb1f2dca633";
+const INITIALIZE_EVENT_SIGNATURE: &str = "98636036cb66a9c19a37435efc1e90142190214e8abeb821bdba3f2990dd4c95";
+const SET_FEE_PROTOCOL_EVENT_SIGNATURE: &str = "973d8d92bb299f4af6ce49b52a8adb85ae46b9f214c4c4fc06ac77401237b133";
+const COLLECT_PROTOCOL_EVENT_SIGNATURE: &str = "596b573906218d3411850b26a6b437d6c4522fdb43d2d2386263f86d50b8b151";
+const INCREASE_OBSERVATION_CARDINALITY_NEXT_EVENT_SIGNATURE: &str = "ac49e518f90a358f652e4400164f05a5d8f7e35e7747279bc3a93dbf584e125a";
+const POOL_EVENT_TYPES: [&str; 9] = ["swap", "mint", "burn", "collect", "flash", "initialize", "set_fee_protocol", "collect_protocol", "increase_observation_cardinality_next"];
+// Event types fetched when the caller does not select any
+const DEFAULT_POOL_EVENT_TYPES: [&str; 5] = ["swap", "mint", "burn", "collect", "flash"];
+// Token address -> (name, symbol, decimals)
+type TokenInfoCache = Arc<Mutex<HashMap<Address, (String, String, Number)>>>;
+// A fetch with its arguments already validated, run by the blocking methods and by their _async variants
+type Query = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Value, Box<dyn std::error::Error + Send + Sync>>> + Send>>;
+
 struct PyValue(Value);
 
 impl IntoPy<PyObject> for PyValue {
@@ -70,6 +98,20 @@ struct SwapEvent {
     tick: i32,  // ABI's int24 can fit in i32 in Rust
 }
 
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Swap", abi = "Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick, uint128 protocolFeesToken0, uint128 protocolFeesToken1)")]
+struct PancakeSwapEvent {
+    sender: Address,
+    to: Address,  // named like SwapEvent so both variants deserialize as a swap
+    amount0: I256,
+    amount1: I256,
+    sqrt_price_x96: U256,
+    liquidity: U256,
+    tick: i32,  // int24 fits in i32
+    protocol_fees_token0: U256,
+    protocol_fees_token1: U256,
+}
+
 #[derive(Debug, EthEvent, Serialize, Deserialize)]
 #[ethevent(name = "Mint", abi = "Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)")]
 struct MintEvent {
@@ -104,12 +146,61 @@ struct CollectEvent {
     amount1: U256,
 }
 
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Flash", abi = "Flash(address indexed sender, address indexed recipient, uint256 amount0, uint256 amount1, uint256 paid0, uint256 paid1)")]
+struct FlashEvent {
+    sender: Address,
+    recipient: Address,
+    amount0: U256,
+    amount1: U256,
+    paid0: U256,
+    paid1: U256,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Initialize", abi = "Initialize(uint160 sqrtPriceX96, int24 tick)")]
+struct InitializeEvent {
+    sqrt_price_x96: U256,
+    tick: i32,  // int24 fits in i32
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "SetFeeProtocol", abi = "SetFeeProtocol(uint8 feeProtocol0Old, uint8 feeProtocol1Old, uint8 feeProtocol0New, uint8 feeProtocol1New)")]
+struct SetFeeProtocolEvent {
+    fee_protocol0_old: u8,
+    fee_protocol1_old: u8,
+    fee_protocol0_new: u8,
+    fee_protocol1_new: u8,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "CollectProtocol", abi = "CollectProtocol(address indexed sender, address indexed recipient, uint128 amount0, uint128 amount1)")]
+struct CollectProtocolEvent {
+    sender: Address,
+    recipient: Address,
+    amount0: U256,
+    amount1: U256,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "IncreaseObservationCardinalityNext", abi = "IncreaseObservationCardinalityNext(uint16 observationCardinalityNextOld, uint16 observationCardinalityNextNew)")]
+struct IncreaseObservationCardinalityNextEvent {
+    observation_cardinality_next_old: u16,
+    observation_cardinality_next_new: u16,
+}
+
 #[derive(Debug, Serialize)]
 enum UniswapEvent {
     Swap(SwapEvent),
+    PancakeSwap(PancakeSwapEvent),
     Mint(MintEvent),
     Burn(BurnEvent),
     Collect(CollectEvent),
+    Flash(FlashEvent),
+    Initialize(InitializeEvent),
+    SetFeeProtocol(SetFeeProtocolEvent),
+    CollectProtocol(CollectProtocolEvent),
+    IncreaseObservationCardinalityNext(IncreaseObservationCardinalityNextEvent),
 }
 
 impl EthLogDecode for UniswapEvent {
@@ -137,9 +228,15 @@ impl EthLogDecode for UniswapEvent {
 fn decode_uniswap_event(log: &Log) -> Result<(UniswapEvent, H256, u64), Box<dyn std::error::Error + Send + Sync>> {
     // Event signatures for Uniswap V3 pool events
     let swap_signature = H256::from_slice(&hex::decode(SWAP_EVENT_SIGNATURE).unwrap());
+    let pancake_swap_signature = H256::from_slice(&hex::decode(PANCAKE_SWAP_EVENT_SIGNATURE).unwrap());
     let mint_signature = H256::from_slice(&hex::decode(MINT_EVENT_SIGNATURE).unwrap());
     let burn_signature = H256::from_slice(&hex::decode(BURN_EVENT_SIGNATURE).unwrap());
     let collect_signature = H256::from_slice(&hex::decode(COLLECT_EVENT_SIGNATURE).unwrap());
+    let flash_signature = H256::from_slice(&hex::decode(FLASH_EVENT_SIGNATURE).unwrap());
+    let initialize_signature = H256::from_slice(&hex::decode(INITIALIZE_EVENT_SIGNATURE).unwrap());
+    let set_fee_protocol_signature = H256::from_slice(&hex::decode(SET_FEE_PROTOCOL_EVENT_SIGNATURE).unwrap());
+    let collect_protocol_signature = H256::from_slice(&hex::decode(COLLECT_PROTOCOL_EVENT_SIGNATURE).unwrap());
+    let increase_observation_cardinality_next_signature = H256::from_slice(&hex::decode(INCREASE_OBSERVATION_CARDINALITY_NEXT_EVENT_SIGNATURE).unwrap());
 
     // Parse the raw log data
     let raw_log = RawLog {
@@ -156,6 +253,11 @@ fn decode_uniswap_event(log: &Log) -> Result<(UniswapEvent, H256, u64), Box<dyn
             Ok(event) => return Ok((UniswapEvent::Swap(event), hash, block_number)),
             Err(err) => return Err(Box::new(err)),
         }
+    } else if log.topics[0] == pancake_swap_signature {
+        match <PancakeSwapEvent as EthLogDecode>::decode_log(&raw_log) {
+            Ok(event) => return Ok((UniswapEvent::PancakeSwap(event), hash, block_number)),
+            Err(err) => return Err(Box::new(err)),
+        }
     } else if log.topics[0] == mint_signature {
         match <MintEvent as EthLogDecode>::decode_log(&raw_log) {
             Ok(event) => return Ok((UniswapEvent::Mint(event), hash, block_number)),
@@ -171,12 +273,224 @@ fn decode_uniswap_event(log: &Log) -> Result<(UniswapEvent, H256, u64), Box<dyn
             Ok(event) => return Ok((UniswapEvent::Collect(event), hash, block_number)),
             Err(err) => return Err(Box::new(err)),
         }
+    } else if log.topics[0] == flash_signature {
+        match <FlashEvent as EthLogDecode>::decode_log(&raw_log) {
+            Ok(event) => return Ok((UniswapEvent::Flash(event), hash, block_number)),
+            Err(err) => return Err(Box::new(err)),
+        }
+    } else if log.topics[0] == initialize_signature {
+        match <InitializeEvent as EthLogDecode>::decode_log(&raw_log) {
+            Ok(event) => return Ok((UniswapEvent::Initialize(event), hash, block_number)),
+            Err(err) => return Err(Box::new(err)),
+        }
+    } else if log.topics[0] == set_fee_protocol_signature {
+        match <SetFeeProtocolEvent as EthLogDecode>::decode_log(&raw_log) {
+            Ok(event) => return Ok((UniswapEvent::SetFeeProtocol(event), hash, block_number)),
+            Err(err) => return Err(Box::new(err)),
+        }
+    } else if log.topics[0] == collect_protocol_signature {
+        match <CollectProtocolEvent as EthLogDecode>::decode_log(&raw_log) {
+            Ok(event) => return Ok((UniswapEvent::CollectProtocol(event), hash, block_number)),
+            Err(err) => return Err(Box::new(err)),
+        }
+    } else if log.topics[0] == increase_observation_cardinality_next_signature {
+        match <IncreaseObservationCardinalityNextEvent as EthLogDecode>::decode_log(&raw_log) {
+            Ok(event) => return Ok((UniswapEvent::IncreaseObservationCardinalityNext(event), hash, block_number)),
+            Err(err) => return Err(Box::new(err)),
+        }
     } else {
         println!("Unknown event signature: {:?}", log);
     }
     Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "Unknown event signature")))
 }
 
+// Topic0 values of an event type, one per ABI variant emitted by the supported protocols
+fn get_pool_event_signature(event_type: &str) -> Option<Vec<H256>> {
+    let signatures = match event_type {
+        "swap" => vec![SWAP_EVENT_SIGNATURE, PANCAKE_SWAP_EVENT_SIGNATURE],
+        "mint" => vec![MINT_EVENT_SIGNATURE],
+        "burn" => vec![BURN_EVENT_SIGNATURE],
+        "collect" => vec![COLLECT_EVENT_SIGNATURE],
+        "flash" => vec![FLASH_EVENT_SIGNATURE],
+        "initialize" => vec![INITIALIZE_EVENT_SIGNATURE],
+        "set_fee_protocol" => vec![SET_FEE_PROTOCOL_EVENT_SIGNATURE],
+        "collect_protocol" => vec![COLLECT_PROTOCOL_EVENT_SIGNATURE],
+        "increase_observation_cardinality_next" => vec![INCREASE_OBSERVATION_CARDINALITY_NEXT_EVENT_SIGNATURE],
+        _ => return None,
+    };
+    Some(signatures.iter().map(|signature| H256::from_str(signature).unwrap()).collect())
+}
+
+fn get_pool_event_signatures(event_types: &[String]) -> Result<Vec<H256>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut signatures = Vec::new();
+    for event_type in event_types {
+        signatures.extend(get_pool_event_signature(event_type).ok_or_else(|| format!("Unknown pool event type: {}", event_type))?);
+    }
+    Ok(signatures)
+}
+
+fn default_pool_event_types() -> Vec<String> {
+    DEFAULT_POOL_EVENT_TYPES.iter().map(|event_type| event_type.to_string()).collect()
+}
+
+fn parse_event_types(event_types: Option<Vec<String>>) -> PyResult<Vec<String>> {
+    validate_event_types(event_types, &POOL_EVENT_TYPES, &DEFAULT_POOL_EVENT_TYPES)
+}
+
+// Validate the event types passed from Python, falling back to the defaults when none are given
+fn validate_event_types(event_types: Option<Vec<String>>, valid_event_types: &[&str], default_event_types: &[&str]) -> PyResult<Vec<String>> {
+    let event_types = match event_types {
+        Some(event_types) => event_types,
+        None => return Ok(default_event_types.iter().map(|event_type| event_type.to_string()).collect()),
+    };
+    if event_types.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err("event_types must not be empty"));
+    }
+    let unknown: Vec<&str> = event_types.iter().filter(|event_type| !valid_event_types.contains(&event_type.as_str())).map(|event_type| event_type.as_str()).collect();
+    if !unknown.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown event types: {}. Valid event types are: {}",
+            unknown.join(", "),
+            valid_event_types.join(", ")
+        )));
+    }
+    Ok(event_types)
+}
+
+// Event types to fetch plus values to match against their indexed parameters.
+// An empty list leaves the parameter unconstrained.
+#[derive(Debug, Clone)]
+struct PoolEventFilter {
+    event_types: Vec<String>,
+    sender: Vec<Address>,
+    recipient: Vec<Address>,
+    owner: Vec<Address>,
+    tick_lower: Vec<i32>,
+    tick_upper: Vec<i32>,
+}
+
+impl Default for PoolEventFilter {
+    fn default() -> Self {
+        PoolEventFilter {
+            event_types: default_pool_event_types(),
+            sender: Vec::new(),
+            recipient: Vec::new(),
+            owner: Vec::new(),
+            tick_lower: Vec::new(),
+            tick_upper: Vec::new(),
+        }
+    }
+}
+
+impl PoolEventFilter {
+    fn has_topic_constraints(&self) -> bool {
+        !self.sender.is_empty() || !self.recipient.is_empty() || !self.owner.is_empty() || !self.tick_lower.is_empty() || !self.tick_upper.is_empty()
+    }
+}
+
+fn tick_to_topic(tick: i32) -> H256 {
+    let mut bytes = [0u8; 32];
+    I256::from(tick).into_raw().to_big_endian(&mut bytes);
+    H256::from(bytes)
+}
+
+// Build one filter per indexed-parameter layout, since topic1-topic3 mean different things across pool events:
+// Swap, Flash and CollectProtocol index (sender, recipient) while Mint, Burn and Collect index (owner, tickLower, tickUpper).
+// Event types that do not index a constrained parameter are left out.
+fn get_pool_event_filters(base_filter: Filter, event_filter: &PoolEventFilter) -> Result<Vec<Filter>, Box<dyn std::error::Error + Send + Sync>> {
+    let event_signatures = get_pool_event_signatures(&event_filter.event_types)?;
+    if !event_filter.has_topic_constraints() {
+        return Ok(vec![base_filter.topic0(event_signatures)]);
+    }
+
+    let mut sender_recipient_signatures = Vec::new();
+    let mut owner_tick_signatures = Vec::new();
+    for event_type in event_filter.event_types.iter() {
+        match event_type.as_str() {
+            "swap" | "flash" | "collect_protocol" => sender_recipient_signatures.extend(get_pool_event_signature(event_type).unwrap()),
+            "mint" | "burn" | "collect" => owner_tick_signatures.extend(get_pool_event_signature(event_type).unwrap()),
+            _ => (),
+        }
+    }
+
+    let mut filters = Vec::new();
+    let uses_owner_ticks = !event_filter.owner.is_empty() || !event_filter.tick_lower.is_empty() || !event_filter.tick_upper.is_empty();
+    let uses_sender_recipient = !event_filter.sender.is_empty() || !event_filter.recipient.is_empty();
+    if !sender_recipient_signatures.is_empty() && !uses_owner_ticks {
+        let mut filter = base_filter.clone().topic0(sender_recipient_signatures);
+        if !event_filter.sender.is_empty() {
+            filter = filter.topic1(event_filter.sender.iter().map(|address| H256::from(*address)).collect::<Vec<H256>>());
+        }
+        if !event_filter.recipient.is_empty() {
+            filter = filter.topic2(event_filter.recipient.iter().map(|address| H256::from(*address)).collect::<Vec<H256>>());
+        }
+        filters.push(filter);
+    }
+    if !owner_tick_signatures.is_empty() && !uses_sender_recipient {
+        let mut filter = base_filter.clone().topic0(owner_tick_signatures);
+        if !event_filter.owner.is_empty() {
+            filter = filter.topic1(event_filter.owner.iter().map(|address| H256::from(*address)).collect::<Vec<H256>>());
+        }
+        if !event_filter.tick_lower.is_empty() {
+            filter = filter.topic2(event_filter.tick_lower.iter().map(|tick| tick_to_topic(*tick)).collect::<Vec<H256>>());
+        }
+        if !event_filter.tick_upper.is_empty() {
+            filter = filter.topic3(event_filter.tick_upper.iter().map(|tick| tick_to_topic(*tick)).collect::<Vec<H256>>());
+        }
+        filters.push(filter);
+    }
+    if filters.is_empty() {
+        return Err("Topic filter does not apply to any of the selected event types".into());
+    }
+    Ok(filters)
+}
+
+// Convert the event types and the topic filter dict passed from Python, e.g. {"owner": "0x...", "tick_lower": [-600, 0]}
+fn parse_pool_event_filter(event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<PoolEventFilter> {
+    let mut result = PoolEventFilter {
+        event_types: parse_event_types(event_types)?,
+        ..Default::default()
+    };
+    let topic_filter = match topic_filter {
+        Some(topic_filter) => topic_filter,
+        None => return Ok(result),
+    };
+    for (key, value) in topic_filter.iter() {
+        let key: String = key.extract()?;
+        match key.as_str() {
+            "sender" | "recipient" | "owner" => {
+                let addresses: Vec<String> = match value.extract::<String>() {
+                    Ok(address) => vec![address],
+                    Err(_) => value.extract()?,
+                };
+                let addresses = addresses.iter().map(|address| Address::from_str(address).map_err(|_| {
+                    pyo3::exceptions::PyValueError::new_err(format!("Invalid address for {}: {}", key, address))
+                })).collect::<PyResult<Vec<Address>>>()?;
+                match key.as_str() {
+                    "sender" => result.sender = addresses,
+                    "recipient" => result.recipient = addresses,
+                    _ => result.owner = addresses,
+                }
+            },
+            "tick_lower" | "tick_upper" => {
+                let ticks: Vec<i32> = match value.extract::<i32>() {
+                    Ok(tick) => vec![tick],
+                    Err(_) => value.extract()?,
+                };
+                if key == "tick_lower" {
+                    result.tick_lower = ticks;
+                } else {
+                    result.tick_upper = ticks;
+                }
+            },
+            _ => return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown topic filter key: {}. Valid keys are: sender, recipient, owner, tick_lower, tick_upper",
+                key
+            ))),
+        }
+    }
+    Ok(result)
+}
 
 #[derive(Debug, EthEvent, Serialize)]
 #[ethevent(name = "PoolCreated", abi = "PoolCreated(address indexed token0, address indexed token1, uint24 indexed fee, int24 tickSpacing, address pool)")]
@@ -193,105 +507,834 @@ struct PoolCreatedEvent {
 pub struct UniswapFetcher {
     provider: Arc<Provider<Http>>,
     block_cache: Arc<Mutex<HashMap<u64, u64>>>,
-    token_info_cache: Arc<Mutex<HashMap<Address, (String, String, Number)>>>,
+    token_info_cache: TokenInfoCache,
+    v4_pool_key_cache: v4::V4PoolKeyCache,
+    // The process-wide runtime of shared_runtime()
+    runtime: &'static Runtime,
+    // Number of BATCH_SIZE block windows fetched at once
+    max_concurrent_batches: usize,
+    protocol: &'static str,
+    // Resolved by the constructor when given a chain id, otherwise from the node's chain id on first use
+    deployments: Arc<tokio::sync::OnceCell<Deployments>>,
 }
 
 #[pymethods]
 impl UniswapFetcher {
     #[new]
-    fn new(rpc_url: String) -> Self {
+    #[pyo3(signature = (rpc_url, chain_id = None, protocol = "uniswap_v3", max_concurrent_batches = MAX_CONCURRENT_BATCHES))]
+    fn new(rpc_url: String, chain_id: Option<u64>, protocol: &str, max_concurrent_batches: usize) -> PyResult<Self> {
+        let protocol = supported_protocols().into_iter().find(|supported| *supported == protocol).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown protocol: {}. Supported protocols: {}",
+                protocol,
+                supported_protocols().join(", ")
+            ))
+        })?;
+        if max_concurrent_batches == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("max_concurrent_batches must be greater than 0"));
+        }
         let provider: Arc<Provider<Http>> = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
         let block_cache: Arc<Mutex<HashMap<u64, u64>>> = Arc::new(Mutex::new(HashMap::new()));
-        let token_info_cache: Arc<Mutex<HashMap<Address, (String, String, Number)>>> = Arc::new(Mutex::new(HashMap::new()));
-        UniswapFetcher { provider, block_cache, token_info_cache }
+        let token_info_cache: TokenInfoCache = Arc::new(Mutex::new(HashMap::new()));
+        let v4_pool_key_cache: v4::V4PoolKeyCache = Arc::new(Mutex::new(HashMap::new()));
+        let runtime = shared_runtime().map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to start the async runtime: {}", e)))?;
+        // A given chain id is checked right away, a detected one on the first call
+        let deployments = match chain_id {
+            Some(chain_id) => Some(resolve_deployments(protocol, chain_id).ok_or_else(|| unsupported_chain(protocol, chain_id))?),
+            None => None,
+        };
+        let deployments = Arc::new(tokio::sync::OnceCell::new_with(deployments));
+        Ok(UniswapFetcher { provider, block_cache, token_info_cache, v4_pool_key_cache, runtime, max_concurrent_batches, protocol, deployments })
     }
 
-    fn get_pool_events_by_token_pairs(&self, py: Python, token_pairs: Vec<(String, String, u32)> , from_block: u64, to_block: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_pool_events_by_token_pairs(self.provider.clone(), self.block_cache.clone(), token_pairs, U64::from(from_block), U64::from(to_block))) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-        }
+    #[getter]
+    fn chain_id(&self, py: Python) -> PyResult<u64> {
+        Ok(self.deployments(py)?.deployment.chain_id)
     }
 
-    fn get_pool_events_by_pool_addresses(&self, py: Python, pool_addresses: Vec<String>, from_block: u64, to_block: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_pool_events_by_pool_addresses(self.provider.clone(), self.block_cache.clone(), pool_addresses.iter().map(|address| Address::from_str(address).unwrap()).collect(), U64::from(from_block), U64::from(to_block))) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-        }
+    #[getter]
+    fn protocol(&self) -> &str {
+        self.protocol
     }
 
-    fn get_signals_by_pool_address(&self, py: Python, pool_address: String, timestamp: u64, interval: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_signals_by_pool_address(self.provider.clone(), Address::from_str(&pool_address).unwrap(), timestamp, interval)) {
-            Ok(result) => Ok(PyValue(result).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-        }
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (token_pairs, from_block, to_block, event_types = None, topic_filter = None, verify_pool_addresses = false))]
+    fn get_pool_events_by_token_pairs(&self, py: Python, token_pairs: Vec<(String, String, u32)>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>, verify_pool_addresses: bool) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_pool_events_by_token_pairs_query(token_pairs, from_block, to_block, event_types, topic_filter, verify_pool_addresses)?)?).into_py(py))
     }
 
-    fn get_block_number_range(&self, _py: Python, start_timestamp: u64, end_timestamp: u64) -> (u64, u64) {
-        let rt = Runtime::new().unwrap();
-        let result = rt.block_on(get_block_number_range(self.provider.clone(), start_timestamp, end_timestamp)).unwrap();
-        (result.0.as_u64(), result.1.as_u64())
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (token_pairs, from_block, to_block, event_types = None, topic_filter = None, verify_pool_addresses = false))]
+    fn get_pool_events_by_token_pairs_async<'p>(&self, py: Python<'p>, token_pairs: Vec<(String, String, u32)>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>, verify_pool_addresses: bool) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_pool_events_by_token_pairs_query(token_pairs, from_block, to_block, event_types, topic_filter, verify_pool_addresses)?)
     }
 
-    fn fetch_pool_data(&self, py: Python, token_pairs: Vec<(String, String, u32)>, start_timestamp: u64, end_timestamp: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(fetch_pool_data(self.provider.clone(), self.block_cache.clone(), token_pairs, start_timestamp, end_timestamp)) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => return Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+    #[pyo3(signature = (pool_addresses, from_block, to_block, event_types = None, topic_filter = None))]
+    fn get_pool_events_by_pool_addresses(&self, py: Python, pool_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_pool_events_by_pool_addresses_query(pool_addresses, from_block, to_block, event_types, topic_filter)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_addresses, from_block, to_block, event_types = None, topic_filter = None))]
+    fn get_pool_events_by_pool_addresses_async<'p>(&self, py: Python<'p>, pool_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_pool_events_by_pool_addresses_query(pool_addresses, from_block, to_block, event_types, topic_filter)?)
+    }
+
+    #[pyo3(signature = (pool_addresses, from_block, to_block, event_types = None, topic_filter = None))]
+    fn iter_pool_events_by_pool_addresses(&self, pool_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<event_stream::PoolEventStream> {
+        if pool_addresses.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("pool_addresses must not be empty"));
         }
+        let pool_addresses = pool_addresses.iter()
+            .map(|address| Address::from_str(address).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid address {}: {}", address, e))))
+            .collect::<PyResult<Vec<Address>>>()?;
+        let event_filter = parse_pool_event_filter(event_types, topic_filter)?;
+        let filters = get_pool_event_filters(Filter::new().address(pool_addresses), &event_filter)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(event_stream::PoolEventStream::new(
+            self.provider.clone(),
+            self.runtime,
+            filters,
+            self.protocol,
+            U64::from(from_block),
+            U64::from(to_block),
+        ))
+    }
+
+    #[pyo3(signature = (pool_address, timestamp, interval, end_timestamp = None))]
+    fn get_signals_by_pool_address(&self, py: Python, pool_address: String, timestamp: u64, interval: u64, end_timestamp: Option<u64>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_signals_by_pool_address_query(pool_address, timestamp, interval, end_timestamp)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_address, timestamp, interval, end_timestamp = None))]
+    fn get_signals_by_pool_address_async<'p>(&self, py: Python<'p>, pool_address: String, timestamp: u64, interval: u64, end_timestamp: Option<u64>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_signals_by_pool_address_query(pool_address, timestamp, interval, end_timestamp)?)
+    }
+
+    fn get_block_number_range(&self, py: Python, start_timestamp: u64, end_timestamp: u64) -> PyResult<(u64, u64)> {
+        let result = self.run(py, self.get_block_number_range_query(start_timestamp, end_timestamp)?)?;
+        Ok((result[0].as_u64().unwrap(), result[1].as_u64().unwrap()))
+    }
+
+    fn get_block_number_range_async<'p>(&self, py: Python<'p>, start_timestamp: u64, end_timestamp: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_block_number_range_query(start_timestamp, end_timestamp)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (token_pairs, start_timestamp, end_timestamp, event_types = None, topic_filter = None, verify_pool_addresses = false))]
+    fn fetch_pool_data(&self, py: Python, token_pairs: Vec<(String, String, u32)>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>, verify_pool_addresses: bool) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.fetch_pool_data_query(token_pairs, start_timestamp, end_timestamp, event_types, topic_filter, verify_pool_addresses)?)?).into_py(py))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (token_pairs, start_timestamp, end_timestamp, event_types = None, topic_filter = None, verify_pool_addresses = false))]
+    fn fetch_pool_data_async<'p>(&self, py: Python<'p>, token_pairs: Vec<(String, String, u32)>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>, verify_pool_addresses: bool) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.fetch_pool_data_query(token_pairs, start_timestamp, end_timestamp, event_types, topic_filter, verify_pool_addresses)?)
     }
 
     fn get_pool_created_events_between_two_timestamps(&self, py: Python, start_timestamp: u64, end_timestamp: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_pool_created_events_between_two_timestamps(self.provider.clone(), self.token_info_cache.clone(), Address::from_str(FACTORY_ADDRESS).unwrap(), start_timestamp, end_timestamp)) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-        }
+        Ok(PyValue(self.run(py, self.get_pool_created_events_between_two_timestamps_query(start_timestamp, end_timestamp)?)?).into_py(py))
+    }
+
+    fn get_pool_created_events_between_two_timestamps_async<'p>(&self, py: Python<'p>, start_timestamp: u64, end_timestamp: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_pool_created_events_between_two_timestamps_query(start_timestamp, end_timestamp)?)
     }
 
     fn get_all_tokens(&self, py: Python, start_timestamp: u64, end_timestamp: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_all_tokens(self.provider.clone(), start_timestamp, end_timestamp)) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
-        }
+        Ok(PyValue(self.run(py, self.get_all_tokens_query(start_timestamp, end_timestamp)?)?).into_py(py))
+    }
+
+    fn get_all_tokens_async<'p>(&self, py: Python<'p>, start_timestamp: u64, end_timestamp: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_all_tokens_query(start_timestamp, end_timestamp)?)
     }
 
     fn get_all_token_pairs(&self, py: Python, start_timestamp: u64, end_timestamp: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_all_token_pairs(self.provider.clone(), start_timestamp, end_timestamp)) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+        Ok(PyValue(self.run(py, self.get_all_token_pairs_query(start_timestamp, end_timestamp)?)?).into_py(py))
+    }
+
+    fn get_all_token_pairs_async<'p>(&self, py: Python<'p>, start_timestamp: u64, end_timestamp: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_all_token_pairs_query(start_timestamp, end_timestamp)?)
+    }
+
+    #[pyo3(signature = (pool_address, start_timestamp, event_types = None, topic_filter = None))]
+    fn get_recent_pool_events(&self, py: Python, pool_address: String, start_timestamp: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_recent_pool_events_query(pool_address, start_timestamp, event_types, topic_filter)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_address, start_timestamp, event_types = None, topic_filter = None))]
+    fn get_recent_pool_events_async<'p>(&self, py: Python<'p>, pool_address: String, start_timestamp: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_recent_pool_events_query(pool_address, start_timestamp, event_types, topic_filter)?)
+    }
+
+    fn get_timestamp_by_block_number(&self, py: Python, block_number: u64) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_timestamp_by_block_number_query(block_number)?)?).into_py(py))
+    }
+
+    fn get_timestamp_by_block_number_async<'p>(&self, py: Python<'p>, block_number: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_timestamp_by_block_number_query(block_number)?)
+    }
+
+    fn get_pool_price_ratios(&self, py: Python, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_pool_price_ratios_query(pool_address, start_timestamp, end_timestamp, interval)?)?).into_py(py))
+    }
+
+    fn get_pool_price_ratios_async<'p>(&self, py: Python<'p>, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_pool_price_ratios_query(pool_address, start_timestamp, end_timestamp, interval)?)
+    }
+
+    fn get_pool_candles(&self, py: Python, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_pool_candles_query(pool_address, start_timestamp, end_timestamp, interval)?)?).into_py(py))
+    }
+
+    fn get_pool_candles_async<'p>(&self, py: Python<'p>, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_pool_candles_query(pool_address, start_timestamp, end_timestamp, interval)?)
+    }
+
+    #[pyo3(signature = (pool_address, block_number = None))]
+    fn get_pool_state(&self, py: Python, pool_address: String, block_number: Option<u64>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_pool_state_query(pool_address, block_number)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_address, block_number = None))]
+    fn get_pool_state_async<'p>(&self, py: Python<'p>, pool_address: String, block_number: Option<u64>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_pool_state_query(pool_address, block_number)?)
+    }
+
+    #[pyo3(signature = (pool_address, block_number = None))]
+    fn get_tick_map(&self, py: Python, pool_address: String, block_number: Option<u64>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_tick_map_query(pool_address, block_number)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_address, block_number = None))]
+    fn get_tick_map_async<'p>(&self, py: Python<'p>, pool_address: String, block_number: Option<u64>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_tick_map_query(pool_address, block_number)?)
+    }
+
+    #[pyo3(signature = (pool_address, to_block, checkpoint = None, verify = false))]
+    fn replay_liquidity(&self, py: Python, pool_address: String, to_block: u64, checkpoint: Option<String>, verify: bool) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.replay_liquidity_query(pool_address, to_block, checkpoint, verify)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_address, to_block, checkpoint = None, verify = false))]
+    fn replay_liquidity_async<'p>(&self, py: Python<'p>, pool_address: String, to_block: u64, checkpoint: Option<String>, verify: bool) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.replay_liquidity_query(pool_address, to_block, checkpoint, verify)?)
+    }
+
+    #[pyo3(signature = (pool_address, window_seconds, block_number = None))]
+    fn get_twap(&self, py: Python, pool_address: String, window_seconds: u32, block_number: Option<u64>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_twap_query(pool_address, window_seconds, block_number)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_address, window_seconds, block_number = None))]
+    fn get_twap_async<'p>(&self, py: Python<'p>, pool_address: String, window_seconds: u32, block_number: Option<u64>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_twap_query(pool_address, window_seconds, block_number)?)
+    }
+
+    fn compute_pool_address(&self, py: Python, token0: String, token1: String, fee: u32) -> PyResult<String> {
+        let deployment = self.deployments(py)?.deployment;
+        let token0 = Address::from_str(&token0).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let token1 = Address::from_str(&token1).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let pool_address = compute_pool_address(deployment.pool_deployer, token0, token1, fee, deployment.init_code_hash);
+        Ok(format!("{:?}", pool_address))
+    }
+
+    #[pyo3(signature = (token_pairs, from_block, to_block, event_types = None))]
+    fn get_v2_pair_events_by_token_pairs(&self, py: Python, token_pairs: Vec<(String, String)>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_v2_pair_events_by_token_pairs_query(token_pairs, from_block, to_block, event_types)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (token_pairs, from_block, to_block, event_types = None))]
+    fn get_v2_pair_events_by_token_pairs_async<'p>(&self, py: Python<'p>, token_pairs: Vec<(String, String)>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_v2_pair_events_by_token_pairs_query(token_pairs, from_block, to_block, event_types)?)
+    }
+
+    #[pyo3(signature = (pair_addresses, from_block, to_block, event_types = None))]
+    fn get_v2_pair_events_by_pair_addresses(&self, py: Python, pair_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_v2_pair_events_by_pair_addresses_query(pair_addresses, from_block, to_block, event_types)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pair_addresses, from_block, to_block, event_types = None))]
+    fn get_v2_pair_events_by_pair_addresses_async<'p>(&self, py: Python<'p>, pair_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_v2_pair_events_by_pair_addresses_query(pair_addresses, from_block, to_block, event_types)?)
+    }
+
+    #[pyo3(signature = (token_pairs, start_timestamp, end_timestamp, event_types = None))]
+    fn fetch_v2_pair_data(&self, py: Python, token_pairs: Vec<(String, String)>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.fetch_v2_pair_data_query(token_pairs, start_timestamp, end_timestamp, event_types)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (token_pairs, start_timestamp, end_timestamp, event_types = None))]
+    fn fetch_v2_pair_data_async<'p>(&self, py: Python<'p>, token_pairs: Vec<(String, String)>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.fetch_v2_pair_data_query(token_pairs, start_timestamp, end_timestamp, event_types)?)
+    }
+
+    fn get_v2_pair_created_events_between_two_timestamps(&self, py: Python, start_timestamp: u64, end_timestamp: u64) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_v2_pair_created_events_between_two_timestamps_query(start_timestamp, end_timestamp)?)?).into_py(py))
+    }
+
+    fn get_v2_pair_created_events_between_two_timestamps_async<'p>(&self, py: Python<'p>, start_timestamp: u64, end_timestamp: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_v2_pair_created_events_between_two_timestamps_query(start_timestamp, end_timestamp)?)
+    }
+
+    fn compute_v2_pair_address(&self, py: Python, token0: String, token1: String) -> PyResult<String> {
+        let deployment = v2_deployment(&self.deployments(py)?)?;
+        let token0 = Address::from_str(&token0).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let token1 = Address::from_str(&token1).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let pair_address = v2::compute_v2_pair_address(deployment.factory_address, token0, token1, deployment.init_code_hash);
+        Ok(format!("{:?}", pair_address))
+    }
+
+    #[pyo3(signature = (pool_ids, from_block, to_block, event_types = None))]
+    fn get_v4_pool_events_by_pool_ids(&self, py: Python, pool_ids: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_v4_pool_events_by_pool_ids_query(pool_ids, from_block, to_block, event_types)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_ids, from_block, to_block, event_types = None))]
+    fn get_v4_pool_events_by_pool_ids_async<'p>(&self, py: Python<'p>, pool_ids: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_v4_pool_events_by_pool_ids_query(pool_ids, from_block, to_block, event_types)?)
+    }
+
+    #[pyo3(signature = (pool_ids, start_timestamp, end_timestamp, event_types = None))]
+    fn fetch_v4_pool_data(&self, py: Python, pool_ids: Vec<String>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.fetch_v4_pool_data_query(pool_ids, start_timestamp, end_timestamp, event_types)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (pool_ids, start_timestamp, end_timestamp, event_types = None))]
+    fn fetch_v4_pool_data_async<'p>(&self, py: Python<'p>, pool_ids: Vec<String>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.fetch_v4_pool_data_query(pool_ids, start_timestamp, end_timestamp, event_types)?)
+    }
+
+    fn get_v4_pool_initialized_events_between_two_timestamps(&self, py: Python, start_timestamp: u64, end_timestamp: u64) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_v4_pool_initialized_events_between_two_timestamps_query(start_timestamp, end_timestamp)?)?).into_py(py))
+    }
+
+    fn get_v4_pool_initialized_events_between_two_timestamps_async<'p>(&self, py: Python<'p>, start_timestamp: u64, end_timestamp: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_v4_pool_initialized_events_between_two_timestamps_query(start_timestamp, end_timestamp)?)
+    }
+
+    #[pyo3(signature = (currency0, currency1, fee, tick_spacing, hooks = "0x0000000000000000000000000000000000000000"))]
+    fn compute_v4_pool_id(&self, currency0: &str, currency1: &str, fee: u32, tick_spacing: i32, hooks: &str) -> PyResult<String> {
+        let currency0 = Address::from_str(currency0).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let currency1 = Address::from_str(currency1).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let hooks = Address::from_str(hooks).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let pool_id = v4::compute_v4_pool_id(currency0, currency1, fee, tick_spacing, hooks);
+        Ok(format!("{:?}", pool_id))
+    }
+
+    fn tick_to_sqrt_price_x96(&self, tick: i32) -> PyResult<String> {
+        let sqrt_price_x96 = price_math::get_sqrt_ratio_at_tick(tick).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(sqrt_price_x96.to_string())
+    }
+
+    fn sqrt_price_x96_to_tick(&self, sqrt_price_x96: &str) -> PyResult<i32> {
+        let sqrt_price_x96 = U256::from_dec_str(sqrt_price_x96).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        price_math::get_tick_at_sqrt_ratio(sqrt_price_x96).map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    #[pyo3(signature = (sqrt_price_x96, token0_decimals = 0, token1_decimals = 0))]
+    fn sqrt_price_x96_to_price(&self, sqrt_price_x96: &str, token0_decimals: u32, token1_decimals: u32) -> PyResult<(String, String)> {
+        let sqrt_price_x96 = U256::from_dec_str(sqrt_price_x96).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(price_strings(&Price::from_sqrt_price_x96(sqrt_price_x96).adjust_decimals(token0_decimals, token1_decimals)))
+    }
+
+    #[pyo3(signature = (from_block, to_block, token_ids = None))]
+    fn get_position_events(&self, py: Python, from_block: u64, to_block: u64, token_ids: Option<Vec<u64>>) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_position_events_query(from_block, to_block, token_ids)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (from_block, to_block, token_ids = None))]
+    fn get_position_events_async<'p>(&self, py: Python<'p>, from_block: u64, to_block: u64, token_ids: Option<Vec<u64>>) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_position_events_query(from_block, to_block, token_ids)?)
+    }
+
+    #[pyo3(signature = (addresses, abi_json, event_names, from_block, to_block))]
+    fn get_events(&self, py: Python, addresses: Vec<String>, abi_json: &str, event_names: Option<Vec<String>>, from_block: u64, to_block: u64) -> PyResult<PyObject> {
+        Ok(PyValue(self.run(py, self.get_events_query(addresses, abi_json, event_names, from_block, to_block)?)?).into_py(py))
+    }
+
+    #[pyo3(signature = (addresses, abi_json, event_names, from_block, to_block))]
+    fn get_events_async<'p>(&self, py: Python<'p>, addresses: Vec<String>, abi_json: &str, event_names: Option<Vec<String>>, from_block: u64, to_block: u64) -> PyResult<&'p PyAny> {
+        self.spawn(py, self.get_events_query(addresses, abi_json, event_names, from_block, to_block)?)
+    }
+}
+
+impl UniswapFetcher {
+    fn block_on<F>(&self, py: Python, future: F) -> PyResult<F::Output>
+    where
+        F: std::future::Future + Send,
+        F::Output: Send,
+    {
+        block_on(py, self.runtime, future)
+    }
+
+    fn get_pool_events_by_token_pairs_query(&self, token_pairs: Vec<(String, String, u32)>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>, verify_pool_addresses: bool) -> PyResult<Query> {
+        let event_filter = parse_pool_event_filter(event_types, topic_filter)?;
+        let (provider, block_cache, deployments) = (self.provider.clone(), self.block_cache.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let result = get_pool_events_by_token_pairs(provider, block_cache, &deployment, token_pairs, U64::from(from_block), U64::from(to_block), event_filter, verify_pool_addresses, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, deployment.protocol))
+        }))
+    }
+
+    fn get_pool_events_by_pool_addresses_query(&self, pool_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<Query> {
+        let event_filter = parse_pool_event_filter(event_types, topic_filter)?;
+        let pool_addresses = pool_addresses.iter().map(|address| Address::from_str(address).unwrap()).collect();
+        let (provider, block_cache, protocol) = (self.provider.clone(), self.block_cache.clone(), self.protocol);
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let result = get_pool_events_by_pool_addresses(provider, block_cache, pool_addresses, U64::from(from_block), U64::from(to_block), event_filter, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, protocol))
+        }))
+    }
+
+    fn get_signals_by_pool_address_query(&self, pool_address: String, timestamp: u64, interval: u64, end_timestamp: Option<u64>) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if interval == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("interval must be greater than 0"));
+        }
+        if end_timestamp.is_some_and(|end_timestamp| end_timestamp < timestamp) {
+            return Err(pyo3::exceptions::PyValueError::new_err("end_timestamp must not be before timestamp"));
         }
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let result = signals::get_signals_by_pool_address(provider, block_cache, pool_address, timestamp, interval, end_timestamp, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_block_number_range_query(&self, start_timestamp: u64, end_timestamp: u64) -> PyResult<Query> {
+        let provider = self.provider.clone();
+        Ok(Box::pin(async move {
+            let (start_block_number, end_block_number) = get_block_number_range(provider, start_timestamp, end_timestamp).await?;
+            Ok(serde_json::json!([start_block_number.as_u64(), end_block_number.as_u64()]))
+        }))
+    }
+
+    fn fetch_pool_data_query(&self, token_pairs: Vec<(String, String, u32)>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>, verify_pool_addresses: bool) -> PyResult<Query> {
+        let event_filter = parse_pool_event_filter(event_types, topic_filter)?;
+        let (provider, block_cache, deployments) = (self.provider.clone(), self.block_cache.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let result = fetch_pool_data(provider, block_cache, &deployment, token_pairs, start_timestamp, end_timestamp, event_filter, verify_pool_addresses, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, deployment.protocol))
+        }))
+    }
+
+    fn get_pool_created_events_between_two_timestamps_query(&self, start_timestamp: u64, end_timestamp: u64) -> PyResult<Query> {
+        let (provider, token_info_cache, deployments) = (self.provider.clone(), self.token_info_cache.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let result = get_pool_created_events_between_two_timestamps(provider, token_info_cache, &deployment, start_timestamp, end_timestamp, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_all_tokens_query(&self, start_timestamp: u64, end_timestamp: u64) -> PyResult<Query> {
+        let (provider, deployments) = (self.provider.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let result = get_all_tokens(provider, &deployment, start_timestamp, end_timestamp, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_all_token_pairs_query(&self, start_timestamp: u64, end_timestamp: u64) -> PyResult<Query> {
+        let (provider, deployments) = (self.provider.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let result = get_all_token_pairs(provider, &deployment, start_timestamp, end_timestamp, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
     }
 
-    fn get_recent_pool_events(&self, py: Python, pool_address: String, start_timestamp: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_recent_pool_events(self.provider.clone(), Address::from_str(&pool_address).unwrap(), start_timestamp)) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+    fn get_recent_pool_events_query(&self, pool_address: String, start_timestamp: u64, event_types: Option<Vec<String>>, topic_filter: Option<&PyDict>) -> PyResult<Query> {
+        let event_filter = parse_pool_event_filter(event_types, topic_filter)?;
+        let pool_address = Address::from_str(&pool_address).unwrap();
+        let (provider, protocol) = (self.provider.clone(), self.protocol);
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let result = get_recent_pool_events(provider, pool_address, start_timestamp, event_filter, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, protocol))
+        }))
+    }
+
+    fn get_timestamp_by_block_number_query(&self, block_number: u64) -> PyResult<Query> {
+        let provider = self.provider.clone();
+        Ok(Box::pin(async move {
+            let result = get_timestamp_by_block_number(provider, block_number).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_pool_price_ratios_query(&self, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).unwrap();
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let result = get_pool_price_ratios(provider, pool_address, start_timestamp, end_timestamp, interval, block_cache, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_pool_candles_query(&self, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if interval == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("interval must be greater than 0"));
         }
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let result = candles::get_pool_candles(provider, block_cache, pool_address, start_timestamp, end_timestamp, interval, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
     }
 
-    fn get_timestamp_by_block_number(&self, py: Python, block_number: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_timestamp_by_block_number(self.provider.clone(), block_number)) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+    fn get_pool_state_query(&self, pool_address: String, block_number: Option<u64>) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let provider = self.provider.clone();
+        Ok(Box::pin(async move {
+            let result = pool_state::get_pool_state(provider, pool_address, block_number.map(U64::from)).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_tick_map_query(&self, pool_address: String, block_number: Option<u64>) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let provider = self.provider.clone();
+        Ok(Box::pin(async move {
+            let result = pool_state::get_tick_map(provider, pool_address, block_number.map(U64::from)).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn replay_liquidity_query(&self, pool_address: String, to_block: u64, checkpoint: Option<String>, verify: bool) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let checkpoint = match checkpoint {
+            Some(checkpoint) => Some(serde_json::from_str::<liquidity_replay::LiquidityReplay>(&checkpoint).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid checkpoint: {}", e)))?),
+            None => None,
+        };
+        let (provider, deployments) = (self.provider.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let replay = liquidity_replay::replay_liquidity(provider.clone(), &deployment, pool_address, U64::from(to_block), checkpoint, max_concurrent_batches).await?;
+            let mut result = serde_json::json!({
+                "pool_address": replay.pool_address,
+                "block_number": replay.block_number,
+                "ticks": replay.tick_liquidity(),
+                "checkpoint": serde_json::to_string(&replay)?,
+            });
+            if verify {
+                let tick_map = pool_state::get_tick_map(provider, pool_address, Some(U64::from(to_block))).await?;
+                result["mismatched_ticks"] = serde_json::json!(replay.mismatched_ticks(&tick_map));
+            }
+            Ok(result)
+        }))
+    }
+
+    fn get_twap_query(&self, pool_address: String, window_seconds: u32, block_number: Option<u64>) -> PyResult<Query> {
+        let pool_address = Address::from_str(&pool_address).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if window_seconds == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("window_seconds must be greater than 0"));
         }
+        let provider = self.provider.clone();
+        Ok(Box::pin(pool_state::get_twap(provider, pool_address, window_seconds, block_number.map(U64::from))))
     }
 
-    fn get_pool_price_ratios(&self, py: Python, pool_address: String, start_timestamp: u64, end_timestamp: u64, interval: u64) -> PyResult<PyObject> {
-        let rt = Runtime::new().unwrap();
-        match rt.block_on(get_pool_price_ratios(self.provider.clone(), Address::from_str(&pool_address).unwrap(), start_timestamp, end_timestamp, interval, self.block_cache.clone())) {
-            Ok(result) => Ok(PyValue(serde_json::json!(result)).into_py(py)),
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+    fn get_v2_pair_events_by_token_pairs_query(&self, token_pairs: Vec<(String, String)>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<Query> {
+        let event_types = v2::parse_v2_event_types(event_types)?;
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        let deployments = self.load_deployments();
+        Ok(Box::pin(async move {
+            let deployment = v2_deployment(&deployments.await?)?;
+            let result = v2::get_v2_pair_events_by_token_pairs(provider, block_cache, &deployment, token_pairs, U64::from(from_block), U64::from(to_block), event_types, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, "uniswap_v2"))
+        }))
+    }
+
+    fn get_v2_pair_events_by_pair_addresses_query(&self, pair_addresses: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<Query> {
+        if pair_addresses.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("pair_addresses must not be empty"));
         }
+        let pair_addresses = pair_addresses.iter()
+            .map(|address| Address::from_str(address).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid address {}: {}", address, e))))
+            .collect::<PyResult<Vec<Address>>>()?;
+        let event_types = v2::parse_v2_event_types(event_types)?;
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let result = v2::get_v2_pair_events_by_pair_addresses(provider, block_cache, pair_addresses, U64::from(from_block), U64::from(to_block), event_types, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, "uniswap_v2"))
+        }))
     }
-    
+
+    fn fetch_v2_pair_data_query(&self, token_pairs: Vec<(String, String)>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>) -> PyResult<Query> {
+        let event_types = v2::parse_v2_event_types(event_types)?;
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        let deployments = self.load_deployments();
+        Ok(Box::pin(async move {
+            let deployment = v2_deployment(&deployments.await?)?;
+            let result = v2::fetch_v2_pair_data(provider, block_cache, &deployment, token_pairs, start_timestamp, end_timestamp, event_types, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, "uniswap_v2"))
+        }))
+    }
+
+    fn get_v2_pair_created_events_between_two_timestamps_query(&self, start_timestamp: u64, end_timestamp: u64) -> PyResult<Query> {
+        let (provider, token_info_cache) = (self.provider.clone(), self.token_info_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        let deployments = self.load_deployments();
+        Ok(Box::pin(async move {
+            let deployment = v2_deployment(&deployments.await?)?;
+            let result = v2::get_v2_pair_created_events_between_two_timestamps(provider, token_info_cache, &deployment, start_timestamp, end_timestamp, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_v4_pool_events_by_pool_ids_query(&self, pool_ids: Vec<String>, from_block: u64, to_block: u64, event_types: Option<Vec<String>>) -> PyResult<Query> {
+        let pool_ids = parse_pool_ids(pool_ids)?;
+        let event_types = v4::parse_v4_event_types(event_types)?;
+        let (provider, block_cache, pool_key_cache) = (self.provider.clone(), self.block_cache.clone(), self.v4_pool_key_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        let deployments = self.load_deployments();
+        Ok(Box::pin(async move {
+            let deployment = v4_deployment(&deployments.await?)?;
+            let result = v4::get_v4_pool_events_by_pool_ids(provider, block_cache, pool_key_cache, &deployment, pool_ids, U64::from(from_block), U64::from(to_block), event_types, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, "uniswap_v4"))
+        }))
+    }
+
+    fn fetch_v4_pool_data_query(&self, pool_ids: Vec<String>, start_timestamp: u64, end_timestamp: u64, event_types: Option<Vec<String>>) -> PyResult<Query> {
+        let pool_ids = parse_pool_ids(pool_ids)?;
+        let event_types = v4::parse_v4_event_types(event_types)?;
+        let (provider, block_cache, pool_key_cache) = (self.provider.clone(), self.block_cache.clone(), self.v4_pool_key_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        let deployments = self.load_deployments();
+        Ok(Box::pin(async move {
+            let deployment = v4_deployment(&deployments.await?)?;
+            let result = v4::fetch_v4_pool_data(provider, block_cache, pool_key_cache, &deployment, pool_ids, start_timestamp, end_timestamp, event_types, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, "uniswap_v4"))
+        }))
+    }
+
+    fn get_v4_pool_initialized_events_between_two_timestamps_query(&self, start_timestamp: u64, end_timestamp: u64) -> PyResult<Query> {
+        let provider = self.provider.clone();
+        let max_concurrent_batches = self.max_concurrent_batches;
+        let deployments = self.load_deployments();
+        Ok(Box::pin(async move {
+            let deployment = v4_deployment(&deployments.await?)?;
+            let result = v4::get_v4_pool_initialized_events_between_two_timestamps(provider, &deployment, start_timestamp, end_timestamp, max_concurrent_batches).await?;
+            Ok(serde_json::json!(result))
+        }))
+    }
+
+    fn get_position_events_query(&self, from_block: u64, to_block: u64, token_ids: Option<Vec<u64>>) -> PyResult<Query> {
+        let token_ids = token_ids.unwrap_or_default().into_iter().map(U256::from).collect();
+        let (provider, block_cache, deployments) = (self.provider.clone(), self.block_cache.clone(), self.load_deployments());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(async move {
+            let deployment = deployments.await?.deployment;
+            let result = positions::get_position_events(provider, block_cache, &deployment, U64::from(from_block), U64::from(to_block), token_ids, max_concurrent_batches).await?;
+            Ok(tag_protocol(result, deployment.protocol))
+        }))
+    }
+
+    fn get_events_query(&self, addresses: Vec<String>, abi_json: &str, event_names: Option<Vec<String>>, from_block: u64, to_block: u64) -> PyResult<Query> {
+        if addresses.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("addresses must not be empty"));
+        }
+        let addresses = addresses.iter()
+            .map(|address| Address::from_str(address).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid address {}: {}", address, e))))
+            .collect::<PyResult<Vec<Address>>>()?;
+        let abi: Abi = serde_json::from_str(abi_json).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid ABI JSON: {}", e)))?;
+        let events = abi_events::select_events(&abi, &event_names.unwrap_or_default()).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        if events.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("The ABI has no events to fetch"));
+        }
+        let (provider, block_cache) = (self.provider.clone(), self.block_cache.clone());
+        let max_concurrent_batches = self.max_concurrent_batches;
+        Ok(Box::pin(abi_events::get_events(provider, block_cache, addresses, events, U64::from(from_block), U64::from(to_block), max_concurrent_batches)))
+    }
+
+    fn run(&self, py: Python, query: Query) -> PyResult<Value> {
+        self.block_on(py, query)?.map_err(query_error)
+    }
+
+    // Run `query` on the shared runtime and return an asyncio future of its result, created on the running event
+    // loop. The result is handed back to the loop thread-safely. Cancelling the future aborts the task, which drops
+    // the query and its in-flight requests.
+    fn spawn<'p>(&self, py: Python<'p>, query: Query) -> PyResult<&'p PyAny> {
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?;
+        let (event_loop_ref, future_ref): (PyObject, PyObject) = (event_loop.into(), future.into());
+        let task = self.runtime.spawn(async move {
+            let result = catch_panic(query).await;
+            Python::with_gil(|py| {
+                let (value, exception) = match result {
+                    Ok(value) => (PyValue(value).into_py(py), py.None()),
+                    Err(e) => (py.None(), query_error(e).into_py(py)),
+                };
+                // Fails only when the loop is already closed, then nobody is waiting for the result
+                let _ = wrap_pyfunction!(set_future_result, py).and_then(|set_future_result| {
+                    event_loop_ref.call_method1(py, "call_soon_threadsafe", (set_future_result, future_ref, value, exception))
+                });
+            });
+        });
+        future.call_method1("add_done_callback", (CancelQuery { task: task.abort_handle() },))?;
+        Ok(future)
+    }
+
+    // The deployments of the node's chain, looked up with eth_chainId on first use unless the chain id was given,
+    // so that constructing a fetcher needs no node. A failed lookup is tried again by the next call.
+    fn load_deployments(&self) -> impl std::future::Future<Output = Result<Deployments, Box<dyn std::error::Error + Send + Sync>>> + Send + 'static {
+        let (provider, deployments, protocol) = (self.provider.clone(), self.deployments.clone(), self.protocol);
+        async move {
+            let deployments = deployments.get_or_try_init(|| async {
+                let chain_id = provider.get_chainid().await.map_err(|e| format!("Failed to detect chain id: {}", e))?.as_u64();
+                resolve_deployments(protocol, chain_id).ok_or_else(|| Box::new(unsupported_chain(protocol, chain_id)) as Box<dyn std::error::Error + Send + Sync>)
+            }).await?;
+            Ok(deployments.clone())
+        }
+    }
+
+    fn deployments(&self, py: Python) -> PyResult<Deployments> {
+        self.block_on(py, self.load_deployments())?.map_err(query_error)
+    }
+}
+
+fn unsupported_chain(protocol: &str, chain_id: u64) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!(
+        "{} is not supported on chain {}. Supported chain ids: {:?}, or 31337 and 1337 for a local fork of Ethereum",
+        protocol,
+        chain_id,
+        supported_chain_ids(protocol)
+    ))
+}
+
+fn v2_deployment(deployments: &Deployments) -> PyResult<V2Deployment> {
+    deployments.v2_deployment.clone().ok_or_else(|| pyo3::exceptions::PyValueError::new_err(format!(
+        "uniswap_v2 is not supported on chain {}. Supported chain ids: {:?}",
+        deployments.deployment.chain_id,
+        supported_v2_chain_ids()
+    )))
+}
+
+fn v4_deployment(deployments: &Deployments) -> PyResult<V4Deployment> {
+    deployments.v4_deployment.clone().ok_or_else(|| pyo3::exceptions::PyValueError::new_err(format!(
+        "uniswap_v4 is not supported on chain {}. Supported chain ids: {:?}",
+        deployments.deployment.chain_id,
+        supported_v4_chain_ids()
+    )))
+}
+
+// Await `query`, turning a panic into an error so that the future waiting for an _async query is still resolved
+async fn catch_panic(query: Query) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    match futures::FutureExt::catch_unwind(std::panic::AssertUnwindSafe(query)).await {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = panic.downcast_ref::<&str>().map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(format!("Query panicked: {}", message).into())
+        },
+    }
+}
+
+// Python exception for a failed query. Errors raised as Python exceptions inside the query, such as a ValueError for
+// a protocol missing on the chain, are kept, anything else becomes a RuntimeError.
+fn query_error(e: Box<dyn std::error::Error + Send + Sync>) -> PyErr {
+    match e.downcast::<PyErr>() {
+        Ok(e) => *e,
+        Err(e) => pyo3::exceptions::PyRuntimeError::new_err(e.to_string()),
+    }
+}
+
+// One Tokio runtime for the whole process, started by the first fetcher. Sharing it keeps the worker threads and the
+// node's keep-alive connections across calls, and since no fetcher owns it, a query started by an _async method keeps
+// running after its fetcher is dropped. It is never shut down, so no Python thread holding the GIL waits for workers.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn shared_runtime() -> std::io::Result<&'static Runtime> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Runtime::new()?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+// Run `future` on `runtime` with the GIL released, so other Python threads keep running during the fetch.
+// Pending signals are checked every SIGNAL_CHECK_INTERVAL: a KeyboardInterrupt drops the future, which cancels its
+// in-flight requests, and is raised instead of the result.
+fn block_on<F>(py: Python, runtime: &Runtime, future: F) -> PyResult<F::Output>
+where
+    F: std::future::Future + Send,
+    F::Output: Send,
+{
+    py.allow_threads(|| {
+        runtime.block_on(async {
+            tokio::pin!(future);
+            loop {
+                tokio::select! {
+                    output = &mut future => return Ok(output),
+                    _ = tokio::time::sleep(SIGNAL_CHECK_INTERVAL) => Python::with_gil(|py| py.check_signals())?,
+                }
+            }
+        })
+    })
+}
+
+// Called on the event loop with the outcome of a query started by an _async method
+#[pyfunction]
+fn set_future_result(future: &PyAny, result: PyObject, exception: Option<&PyAny>) -> PyResult<()> {
+    // The future may have been cancelled while the result was on its way
+    if future.call_method0("done")?.is_true()? {
+        return Ok(());
+    }
+    match exception {
+        Some(exception) => future.call_method1("set_exception", (exception,))?,
+        None => future.call_method1("set_result", (result,))?,
+    };
+    Ok(())
+}
+
+// Done callback of the future returned by an _async method, aborting its query when the future is cancelled
+#[pyclass]
+struct CancelQuery {
+    task: tokio::task::AbortHandle,
+}
+
+#[pymethods]
+impl CancelQuery {
+    fn __call__(&self, future: &PyAny) -> PyResult<()> {
+        if future.call_method0("cancelled")?.is_true()? {
+            self.task.abort();
+        }
+        Ok(())
+    }
+}
+
+fn parse_pool_ids(pool_ids: Vec<String>) -> PyResult<Vec<H256>> {
+    if pool_ids.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err("pool_ids must not be empty"));
+    }
+    pool_ids.iter()
+        .map(|pool_id| H256::from_str(pool_id).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid pool id {}: {}", pool_id, e))))
+        .collect()
+}
+
+// Record which protocol an event envelope came from
+fn tag_protocol(mut result: Value, protocol: &str) -> Value {
+    if let Some(object) = result.as_object_mut() {
+        object.insert("protocol".to_string(), Value::String(protocol.to_string()));
+    }
+    result
 }
 
 fn get_pool_abi() -> Abi {
@@ -306,8 +1349,89 @@ fn get_token_abis() -> Vec<(String, Abi)> {
     vec![("erc20".to_string(), erc20_abi), ("erc721".to_string(), erc721_abi), ("dstoken".to_string(), dstoken_abi)]
 }
 
-
-async fn get_pool_address(provider: Arc<Provider<Http>>, factory_address: Address, token0: Address, token1: Address, fee: u32) -> Result<Address, Box<dyn std::error::Error + Send + Sync>> {
+
+// Derive the pool address the pool deployer creates with CREATE2, without any RPC call
+fn compute_pool_address(pool_deployer: Address, token_a: Address, token_b: Address, fee: u32, init_code_hash: H256) -> Address {
+    let (token0, token1) = if token_a < token_b { (token_a, token_b) } else { (token_b, token_a) };
+    let salt = ethers::utils::keccak256(ethers::abi::encode(&[
+        ethers::abi::Token::Address(token0),
+        ethers::abi::Token::Address(token1),
+        ethers::abi::Token::Uint(U256::from(fee)),
+    ]));
+    ethers::utils::get_create2_address_from_hash(pool_deployer, salt, init_code_hash)
+}
+
+#[derive(Debug, Serialize)]
+struct PoolResolution {
+    token0: Address,
+    token1: Address,
+    fee: u32,
+    pool_address: Option<Address>,
+    status: &'static str,  // "found", "missing" or "invalid_fee_tier"
+}
+
+// Resolve the pool of every (token0, token1, fee) pair and check that it is deployed at `block_number`.
+// Addresses are derived with CREATE2; `verify_pool_addresses` asks the factory instead. The check of each pair is one
+// RPC call, with up to `max_concurrent_batches` of them in flight.
+async fn resolve_pool_addresses(
+    provider: Arc<Provider<Http>>,
+    deployment: &Deployment,
+    token_pairs: Vec<(String, String, u32)>,
+    block_number: U64,
+    verify_pool_addresses: bool,
+    max_concurrent_batches: usize,
+) -> Result<Vec<PoolResolution>, Box<dyn std::error::Error + Send + Sync>> {
+    let factory_address = deployment.factory_address;
+    let pool_deployer = deployment.pool_deployer;
+    let init_code_hash = deployment.init_code_hash;
+    let fee_tiers = deployment.fee_tiers;
+
+    stream::iter(token_pairs).map(|(token0, token1, fee)| {
+        let provider = provider.clone();
+        async move {
+            let token0_address = Address::from_str(&token0)?;
+            let token1_address = Address::from_str(&token1)?;
+            let mut resolution = PoolResolution {
+                token0: token0_address,
+                token1: token1_address,
+                fee,
+                pool_address: None,
+                status: "missing",
+            };
+            if !fee_tiers.iter().any(|(fee_tier, _)| *fee_tier == fee) {
+                resolution.status = "invalid_fee_tier";
+                return Ok(resolution);
+            }
+            if token0_address == token1_address {
+                return Ok(resolution);
+            }
+            let pool_address = compute_pool_address(pool_deployer, token0_address, token1_address, fee, init_code_hash);
+            resolution.pool_address = Some(pool_address);
+            if verify_pool_addresses {
+                // The factory returns the zero address for pairs without a pool
+                let factory_pool_address = get_pool_address(provider.clone(), factory_address, token0_address, token1_address, fee, block_number).await?;
+                if factory_pool_address.is_zero() {
+                    return Ok(resolution);
+                }
+                if factory_pool_address != pool_address {
+                    resolution.pool_address = Some(factory_pool_address);
+                }
+            } else {
+                let code = provider.get_code(pool_address, Some(block_number.into())).await?;
+                if code.is_empty() {
+                    return Ok(resolution);
+                }
+            }
+            resolution.status = "found";
+            Ok(resolution) as Result<PoolResolution, Box<dyn std::error::Error + Send + Sync>>
+        }
+    })
+    .buffered(max_concurrent_batches)
+    .try_collect()
+    .await
+}
+
+async fn get_pool_address(provider: Arc<Provider<Http>>, factory_address: Address, token0: Address, token1: Address, fee: u32, block_number: U64) -> Result<Address, Box<dyn std::error::Error + Send + Sync>> {
     // Load the Uniswap V3 factory ABI
     let abi_json = include_str!("contracts/uniswap_pool_factory_abi.json");
     let abi: Abi = serde_json::from_str(abi_json)?;
@@ -316,111 +1440,215 @@ async fn get_pool_address(provider: Arc<Provider<Http>>, factory_address: Addres
     let factory = Contract::new(factory_address, abi, provider.clone());
 
     // Call the getPool function
-    let pool_address: Address = factory.method("getPool", (token0, token1, U256::from(fee)))?.call().await?;
+    let pool_address: Address = factory.method("getPool", (token0, token1, U256::from(fee)))?.block(block_number).call().await?;
 
     Ok(pool_address)
 }
 
 
+// Consecutive inclusive windows of BATCH_SIZE blocks covering `from_block` to `to_block`, in block order
+fn block_windows(from_block: U64, to_block: U64) -> Vec<(U64, U64)> {
+    let mut windows = Vec::new();
+    let mut current_block_number = from_block;
+    while current_block_number <= to_block {
+        let next_block_number = min(current_block_number + BATCH_SIZE, to_block);
+        windows.push((current_block_number, next_block_number));
+        current_block_number = next_block_number + 1;
+    }
+    windows
+}
+
+// Fetch the logs matching `filter` between `from_block` and `to_block` in windows of BATCH_SIZE blocks, with up to
+// `max_concurrent_batches` windows in flight. The logs are returned in window order whichever window finishes first.
+async fn get_logs_in_batches(
+    provider: Arc<Provider<Http>>,
+    filter: Filter,
+    from_block: U64,
+    to_block: U64,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Log>, Box<dyn std::error::Error + Send + Sync>> {
+    let batches: Vec<Vec<Log>> = stream::iter(block_windows(from_block, to_block))
+        .map(|(window_start, window_end)| {
+            let provider = provider.clone();
+            let batch_filter = filter.clone().from_block(window_start).to_block(window_end);
+            async move { provider.get_logs(&batch_filter).await }
+        })
+        .buffered(max_concurrent_batches)
+        .try_collect()
+        .await?;
+    Ok(batches.into_iter().flatten().collect())
+}
+
+// Fetch pool event logs of the given types, ordered by block number and log index
+async fn get_pool_logs(
+    provider: Arc<Provider<Http>>,
+    base_filter: Filter,
+    from_block: U64,
+    to_block: U64,
+    event_filter: &PoolEventFilter,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Log>, Box<dyn std::error::Error + Send + Sync>> {
+    let filters = get_pool_event_filters(base_filter, event_filter)?;
+    let mut logs = Vec::new();
+    for filter in filters {
+        logs.extend(get_logs_in_batches(provider.clone(), filter, from_block, to_block, max_concurrent_batches).await?);
+    }
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    Ok(logs)
+}
+
+// Swap logs of a pool between two blocks in both the Uniswap and PancakeSwap layouts, ordered by block number and log index
+async fn get_swap_logs(
+    provider: Arc<Provider<Http>>,
+    pool_address: Address,
+    from_block: U64,
+    to_block: U64,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Log>, Box<dyn std::error::Error + Send + Sync>> {
+    let filter = Filter::new()
+        .address(pool_address)
+        .topic0(vec![
+            H256::from_str(SWAP_EVENT_SIGNATURE).unwrap(),
+            H256::from_str(PANCAKE_SWAP_EVENT_SIGNATURE).unwrap(),
+        ]);
+    let mut logs = get_logs_in_batches(provider, filter, from_block, to_block, max_concurrent_batches).await?;
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    Ok(logs)
+}
+
+// sqrtPriceX96 after a swap and the swapped token0 and token1 amounts
+fn decode_swap(log: &Log) -> Result<(U256, I256, I256), Box<dyn std::error::Error + Send + Sync>> {
+    match decode_uniswap_event(log)?.0 {
+        UniswapEvent::Swap(event) => Ok((event.sqrt_price_x96, event.amount0, event.amount1)),
+        UniswapEvent::PancakeSwap(event) => Ok((event.sqrt_price_x96, event.amount0, event.amount1)),
+        _ => Err("Not a swap event".into()),
+    }
+}
+
+// sqrtPriceX96 after the last swap in the BATCH_SIZE blocks before `block_number`, to start a price series with
+async fn get_previous_sqrt_price(
+    provider: Arc<Provider<Http>>,
+    pool_address: Address,
+    block_number: U64,
+    max_concurrent_batches: usize,
+) -> Result<Option<U256>, Box<dyn std::error::Error + Send + Sync>> {
+    if block_number.is_zero() {
+        return Ok(None);
+    }
+    let from_block = block_number.saturating_sub(U64::from(BATCH_SIZE));
+    let logs = get_swap_logs(provider, pool_address, from_block, block_number - 1, max_concurrent_batches).await?;
+    match logs.last() {
+        Some(log) => Ok(Some(decode_swap(log)?.0)),
+        None => Ok(None),
+    }
+}
+
+// Timestamp of a block, read through the shared block cache
+async fn get_block_timestamp(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    block_number: u64,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let mut cache = block_cache.lock().await;
+    if let Some(&cached_timestamp) = cache.get(&block_number) {
+        return Ok(cached_timestamp);
+    }
+    let block = provider.get_block(block_number).await?.ok_or("Block not found")?;
+    let timestamp = block.timestamp.as_u64();
+    cache.insert(block_number, timestamp);
+    Ok(timestamp)
+}
+
+// Timestamp closing the `interval` a timestamp is aggregated into
+fn interval_end_timestamp(timestamp: u64, interval: u64) -> u64 {
+    (timestamp + interval) / interval * interval
+}
+
 async fn get_pool_events_by_pool_addresses(
     provider: Arc<Provider<Http>>,
     block_cache: Arc<Mutex<HashMap<u64, u64>>>,
     pool_addresses: Vec<H160>,
     from_block: U64,
-    to_block: U64
+    to_block: U64,
+    event_filter: PoolEventFilter,
+    max_concurrent_batches: usize,
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-    let mut current_block_number = from_block;
-    let mut logs = Vec::new();
-    while current_block_number <= to_block {
-        let next_block_number = min(current_block_number + BATCH_SIZE, to_block);
-        let filter = Filter::new()
-            .address(pool_addresses.clone())
-            .from_block(current_block_number)
-            .to_block(next_block_number)
-            .topic0(vec![
-                H256::from_str(SWAP_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(MINT_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(BURN_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(COLLECT_EVENT_SIGNATURE).unwrap(),
-            ]);
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
+    let logs = get_pool_logs(provider.clone(), Filter::new().address(pool_addresses), from_block, to_block, &event_filter, max_concurrent_batches).await?;
     println!("fetched pool events from_block: {:?}, to_block: {:?}", from_block, to_block);
     let events = serialize_logs(logs, provider.clone(), block_cache.clone()).await?;
     Ok(events)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn get_pool_events_by_token_pairs(
     provider: Arc<Provider<Http>>,
     block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    deployment: &Deployment,
     token_pairs: Vec<(String, String, u32)>,
     from_block: U64,
     to_block: U64,
+    event_filter: PoolEventFilter,
+    verify_pool_addresses: bool,
+    max_concurrent_batches: usize,
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
 
-    // Get the Uniswap V3 factory address
-    let factory_address = Address::from_str("0x1F98431c8aD98523631AE4a59f267346ea31F984")?;
-
-    let futures = token_pairs.into_iter().map(|(token0, token1, fee)| {
-        let provider = provider.clone();
-        async move {
-            let token0_address = Address::from_str(&token0)?;
-            let token1_address = Address::from_str(&token1)?;
-            let pool_address = get_pool_address(provider.clone(), factory_address, token0_address, token1_address, fee).await?;
-            Ok(pool_address) as Result<Address, Box<dyn std::error::Error + Send + Sync>>
-        }
-    });
-
-    let pool_addresses_results = join_all(futures).await;
-
-    let mut pool_addresses = Vec::new();
-    for result in pool_addresses_results {
-        match result {
-            Ok(pool_address) => pool_addresses.push(pool_address),
-            Err(e) => return Err(e),
-        }
-    }
+    let pools = resolve_pool_addresses(provider.clone(), deployment, token_pairs, to_block, verify_pool_addresses, max_concurrent_batches).await?;
+    let pool_addresses: Vec<Address> = pools.iter()
+        .filter(|pool| pool.status == "found")
+        .filter_map(|pool| pool.pool_address)
+        .collect();
 
     println!("Fetched pool address: {:?}", pool_addresses);
 
-    let events = get_pool_events_by_pool_addresses(provider.clone(), block_cache.clone(), pool_addresses, from_block, to_block).await?;
+    // An empty address list would make the node return logs from every contract, so skip the query entirely
+    let mut events = if pool_addresses.is_empty() {
+        serialize_logs(Vec::new(), provider.clone(), block_cache.clone()).await?
+    } else {
+        get_pool_events_by_pool_addresses(provider.clone(), block_cache.clone(), pool_addresses, from_block, to_block, event_filter, max_concurrent_batches).await?
+    };
+    events.as_object_mut().unwrap().insert("pools".to_string(), serde_json::json!(pools));
     Ok(events)
-    
+
 }
 
 async fn serialize_logs(logs: Vec<Log>, provider: Arc::<Provider<Http>>, block_cache: Arc<Mutex<HashMap<u64, u64>>>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    serialize_logs_with(logs, provider, block_cache, |log| {
+        let (uniswap_event, _, _) = decode_uniswap_event(log)?;
+        let event = match uniswap_event {
+            UniswapEvent::Swap(event) => serde_json::json!({ "type": "swap", "data": event }),
+            UniswapEvent::PancakeSwap(event) => serde_json::json!({ "type": "swap", "data": event }),
+            UniswapEvent::Mint(event) => serde_json::json!({ "type": "mint", "data": event }),
+            UniswapEvent::Burn(event) => serde_json::json!({ "type": "burn", "data": event }),
+            UniswapEvent::Collect(event) => serde_json::json!({ "type": "collect", "data": event }),
+            UniswapEvent::Flash(event) => serde_json::json!({ "type": "flash", "data": event }),
+            UniswapEvent::Initialize(event) => serde_json::json!({ "type": "initialize", "data": event }),
+            UniswapEvent::SetFeeProtocol(event) => serde_json::json!({ "type": "set_fee_protocol", "data": event }),
+            UniswapEvent::CollectProtocol(event) => serde_json::json!({ "type": "collect_protocol", "data": event }),
+            UniswapEvent::IncreaseObservationCardinalityNext(event) => serde_json::json!({ "type": "increase_observation_cardinality_next", "data": event }),
+        };
+        Ok(event)
+    }).await
+}
+
+// Decode every log with `decode`, which returns the `{ "type", "data" }` event object,
+// and wrap the events in the envelope shared by all event fetchers
+async fn serialize_logs_with<F>(logs: Vec<Log>, provider: Arc::<Provider<Http>>, block_cache: Arc<Mutex<HashMap<u64, u64>>>, decode: F) -> Result<Value, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Fn(&Log) -> Result<Value, Box<dyn std::error::Error + Send + Sync>>,
+{
     let mut data = Vec::new();
     for log in logs {
-        match decode_uniswap_event(&log) {
-            Ok(event) => {
-                let (uniswap_event, transaction_hash, block_number) = event;
-                let timestamp = {
-                    let mut cache = block_cache.lock().await;
-                    if let Some(&cached_timestamp) = cache.get(&block_number) {
-                        cached_timestamp
-                    } else {
-                        let block = provider.get_block(block_number).await?.ok_or("Block not found")?;
-                        let timestamp = block.timestamp.as_u64();
-                        cache.insert(block_number, timestamp);
-                        timestamp
-                    }
-                };
-                let mut uniswap_event_with_metadata = match uniswap_event {
-                    UniswapEvent::Swap(event) => serde_json::json!({ "event": { "type": "swap", "data": event } }),
-                    UniswapEvent::Mint(event) => serde_json::json!({ "event": { "type": "mint", "data": event } }),
-                    UniswapEvent::Burn(event) => serde_json::json!({ "event": { "type": "burn", "data": event } }),
-                    UniswapEvent::Collect(event) => serde_json::json!({ "event": { "type": "collect", "data": event } }),
-                };
-                uniswap_event_with_metadata.as_object_mut().unwrap().insert("transaction_hash".to_string(), serde_json::Value::String(hex::encode(transaction_hash.as_bytes())));
-                uniswap_event_with_metadata.as_object_mut().unwrap().insert("block_number".to_string(), serde_json::Value::Number(serde_json::Number::from(block_number)));
-                uniswap_event_with_metadata.as_object_mut().unwrap().insert("timestamp".to_string(), serde_json::Value::Number(serde_json::Number::from(timestamp)));
-                uniswap_event_with_metadata.as_object_mut().unwrap().insert("pool_address".to_string(), serde_json::Value::String(format!("{:?}", log.address)));
-                data.push(uniswap_event_with_metadata);
-            },
-            Err(e) => return Err(e),
-        }
+        let event = decode(&log)?;
+        let transaction_hash = log.transaction_hash.ok_or("Missing transaction hash")?;
+        let block_number = log.block_number.ok_or("Missing block number")?.as_u64();
+        let timestamp = get_block_timestamp(provider.clone(), block_cache.clone(), block_number).await?;
+        data.push(serde_json::json!({
+            "event": event,
+            "transaction_hash": hex::encode(transaction_hash.as_bytes()),
+            "block_number": block_number,
+            "timestamp": timestamp,
+            "pool_address": format!("{:?}", log.address),
+        }));
     }
 
     let mut hasher = Sha256::new();
@@ -530,40 +1758,32 @@ async fn get_block_number_from_timestamp(
     Ok(low)
 }
 
-async fn fetch_pool_data(provider: Arc::<Provider<Http>>, block_cache: Arc<Mutex<HashMap<u64, u64>>>, token_pairs: Vec<(String, String, u32)>, start_timestamp: u64, end_timestamp: u64) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+#[allow(clippy::too_many_arguments)]
+async fn fetch_pool_data(provider: Arc::<Provider<Http>>, block_cache: Arc<Mutex<HashMap<u64, u64>>>, deployment: &Deployment, token_pairs: Vec<(String, String, u32)>, start_timestamp: u64, end_timestamp: u64, event_filter: PoolEventFilter, verify_pool_addresses: bool, max_concurrent_batches: usize) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
     // let date_str = "2024-09-27 19:34:56";
     let (from_block, to_block) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
-    let pool_events = get_pool_events_by_token_pairs(provider.clone(), block_cache.clone(), token_pairs, from_block, to_block,).await?;
+    let pool_events = get_pool_events_by_token_pairs(provider.clone(), block_cache.clone(), deployment, token_pairs, from_block, to_block, event_filter, verify_pool_addresses, max_concurrent_batches).await?;
     Ok(pool_events)
 }
 
 async fn get_pool_created_events_between_two_timestamps(
     provider: Arc<Provider<Http>>,
-    token_info_cache: Arc<Mutex<HashMap<Address, (String, String, Number)>>>,
-    factory_address: Address,
+    token_info_cache: TokenInfoCache,
+    deployment: &Deployment,
     start_timestamp: u64,
     end_timestamp: u64,
+    max_concurrent_batches: usize,
 ) -> Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>> {
     println!("{} | Fetching pool created events between two timestamps", Utc::now());
+    let factory_address = deployment.factory_address;
     let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
-    let mut current_block_number = start_block_number;
-    let mut logs = Vec::new();
-    let erc20_abi: Abi = serde_json::from_str(include_str!("contracts/erc20_abi.json"))?;
-    let erc721_abi: Abi = serde_json::from_str(include_str!("contracts/erc721_abi.json"))?;
-    let dstoken_abi: Abi = serde_json::from_str(include_str!("contracts/dstoken_abi.json"))?;
-    let abis: Vec<(String, Abi)> = vec![("erc20".to_string(), erc20_abi), ("erc721".to_string(), erc721_abi), ("dstoken".to_string(), dstoken_abi)];
-
-    while current_block_number <= end_block_number {
-        let next_block_number = min(current_block_number + BATCH_SIZE as u64, end_block_number);
-        let filter = Filter::new()
-            .address(factory_address)
-            .topic0(H256::from_str(POOL_CREATED_SIGNATURE).unwrap())
-            .from_block(current_block_number)
-            .to_block(next_block_number);
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
+    // No pools exist before the factory was deployed
+    let start_block_number = max(start_block_number, U64::from(deployment.deployment_block));
+    let filter = Filter::new()
+        .address(factory_address)
+        .topic0(H256::from_str(POOL_CREATED_SIGNATURE).unwrap());
+    let logs = get_logs_in_batches(provider.clone(), filter, start_block_number, end_block_number, max_concurrent_batches).await?;
+    let abis = get_token_abis();
 
     let mut pool_created_events = Vec::new();
     for log in logs {
@@ -574,26 +1794,8 @@ async fn get_pool_created_events_between_two_timestamps(
 
         if log.topics[0] == H256::from_str(POOL_CREATED_SIGNATURE).unwrap() {
             let pool_created_event = <PoolCreatedEvent as EthLogDecode>::decode_log(&raw_log)?;
-            let token0_info = {
-                let mut cache = token_info_cache.lock().await;
-                if let Some(cached_token_info) = cache.get(&pool_created_event.token0) {
-                    cached_token_info.clone()
-                } else {
-                    let token_info = get_token_info(provider.clone(), pool_created_event.token0, abis.clone()).await.unwrap_or_else(|_| ("".to_string(), "".to_string(), 0.into()));
-                    cache.insert(pool_created_event.token0, token_info.clone());
-                    token_info
-                }
-            };
-            let token1_info = {
-                let mut cache = token_info_cache.lock().await;
-                if let Some(cached_token_info) = cache.get(&pool_created_event.token1) {
-                    cached_token_info.clone()
-                } else {
-                    let token_info = get_token_info(provider.clone(), pool_created_event.token1, abis.clone()).await.unwrap_or_else(|_| ("".to_string(), "".to_string(), 0.into()));
-                    cache.insert(pool_created_event.token1, token_info.clone());
-                    token_info
-                }
-            };
+            let token0_info = get_cached_token_info(provider.clone(), token_info_cache.clone(), pool_created_event.token0, abis.clone()).await;
+            let token1_info = get_cached_token_info(provider.clone(), token_info_cache.clone(), pool_created_event.token1, abis.clone()).await;
             pool_created_events.push(serde_json::json!({
                 "token0": {
                     "address": pool_created_event.token0,
@@ -611,6 +1813,7 @@ async fn get_pool_created_events_between_two_timestamps(
                 "tick_spacing": pool_created_event.tick_spacing,
                 "pool_address": pool_created_event.pool,
                 "block_number": log.block_number.unwrap().as_u64(),
+                "protocol": deployment.protocol,
             }));
         }
     }
@@ -618,72 +1821,6 @@ async fn get_pool_created_events_between_two_timestamps(
     Ok(pool_created_events)
 }
 
-async fn get_signals_by_pool_address(
-    provider: Arc<Provider<Http>>,
-    pool_address: Address,
-    timestamp: u64,
-    interval: u64,
-) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-    let average_block_time = get_average_block_time(provider.clone()).await?;
-    let start_block_number = get_block_number_from_timestamp(provider.clone(), timestamp, average_block_time).await?;
-    let end_block_number = start_block_number + interval as u64;
-    let mut current_block_number = start_block_number;
-    let mut logs = Vec::new();
-    while current_block_number <= end_block_number {
-        let next_block_number = min(current_block_number + BATCH_SIZE as u64, end_block_number);
-        let filter = Filter::new()
-            .address(pool_address)
-            .from_block(current_block_number)
-            .to_block(next_block_number)
-            .topic0(vec![
-                H256::from_str(SWAP_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(MINT_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(BURN_EVENT_SIGNATURE).unwrap(),
-            ]);
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
-    let events = serialize_logs(logs, provider.clone(), Arc::new(Mutex::new(HashMap::new()))).await?;
-    let data = events["data"].as_array().unwrap();
-    let mut price: f64 = 0.0;
-    let mut volume: I256 = I256::from(0);
-    let mut liquidity: BigInt = BigInt::from(0);
-    let mut swap_event_count: i32 = 0;
-    
-    for event in data {
-        let event_type = event["event"]["type"].as_str().unwrap();
-        let event_data = event["event"]["data"].clone();
-        match event_type {
-            "swap" => {
-                let swap_event: SwapEvent = serde_json::from_value(event_data)?;
-                let sqrt_price = ( swap_event.sqrt_price_x96 / 2u128.pow(96) ).as_u128() as f64;
-                price = price + sqrt_price * sqrt_price;
-                swap_event_count = swap_event_count + 1;
-                volume = volume + swap_event.amount0.abs() + swap_event.amount1.abs();
-            },
-            "mint" => {
-                let mint_event: MintEvent = serde_json::from_value(event_data)?;
-                liquidity = liquidity + BigInt::parse_bytes(mint_event.amount.to_string().as_bytes(), 10).unwrap();
-            },
-            "burn" => {
-                let burn_event: BurnEvent = serde_json::from_value(event_data)?;
-                liquidity = liquidity - BigInt::parse_bytes(burn_event.amount.to_string().as_bytes(), 10).unwrap();
-            },
-            _ => (),
-        }
-    }
-    if swap_event_count > 0 {
-        price = price / swap_event_count as f64;
-    }
-    let signals = serde_json::json!({
-        "price": price.to_string(),
-        "volume": volume.to_string(),
-        "liquidity": liquidity.to_string(),
-    });
-    Ok(signals)
-}
-
 async fn get_token_info(provider: Arc<Provider<Http>>, token_address: Address, abis: Vec<(String, Abi)>) -> Result<(String, String, Number), Box<dyn std::error::Error + Send + Sync>> {
     
     let contracts: Vec<_> = abis.iter().map(|abi| (abi.0.clone(), Contract::new(token_address, abi.1.clone(), provider.clone()))).collect();
@@ -730,26 +1867,37 @@ async fn get_token_info(provider: Arc<Provider<Http>>, token_address: Address, a
     
 }
 
+// Token info from the cache, fetched on a miss; tokens without readable metadata get empty values
+async fn get_cached_token_info(
+    provider: Arc<Provider<Http>>,
+    token_info_cache: TokenInfoCache,
+    token_address: Address,
+    abis: Vec<(String, Abi)>,
+) -> (String, String, Number) {
+    let mut cache = token_info_cache.lock().await;
+    if let Some(cached_token_info) = cache.get(&token_address) {
+        cached_token_info.clone()
+    } else {
+        let token_info = get_token_info(provider.clone(), token_address, abis).await.unwrap_or_else(|_| ("".to_string(), "".to_string(), 0.into()));
+        cache.insert(token_address, token_info.clone());
+        token_info
+    }
+}
+
 async fn get_all_token_pairs(
     provider: Arc<Provider<Http>>,
+    deployment: &Deployment,
     start_timestamp: u64,
-    end_timestamp: u64
+    end_timestamp: u64,
+    max_concurrent_batches: usize
 ) -> Result<Vec<(Address, Address, u32, Address)>, Box<dyn std::error::Error + Send + Sync>> {
-    let factory_address = Address::from_str(FACTORY_ADDRESS)?;
+    let factory_address = deployment.factory_address;
     let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
-    let mut logs = Vec::new();
-    let mut current_block_number = start_block_number;
-    while current_block_number <= end_block_number {
-        let next_block_number = min(current_block_number + BATCH_SIZE, end_block_number);
-        let filter = Filter::new()
-            .address(factory_address)
-            .topic0(H256::from_str(POOL_CREATED_SIGNATURE).unwrap())
-            .from_block(current_block_number)
-            .to_block(next_block_number);
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
+    let start_block_number = max(start_block_number, U64::from(deployment.deployment_block));
+    let filter = Filter::new()
+        .address(factory_address)
+        .topic0(H256::from_str(POOL_CREATED_SIGNATURE).unwrap());
+    let logs = get_logs_in_batches(provider.clone(), filter, start_block_number, end_block_number, max_concurrent_batches).await?;
 
     let mut token_pairs = Vec::new();
     for log in logs {
@@ -768,25 +1916,19 @@ async fn get_all_token_pairs(
 
 async fn get_all_tokens(
     provider: Arc<Provider<Http>>,
+    deployment: &Deployment,
     start_timestamp: u64,
-    end_timestamp: u64
+    end_timestamp: u64,
+    max_concurrent_batches: usize
 ) -> Result<HashSet<Address>, Box<dyn std::error::Error + Send + Sync>> {
     println!("{} | Fetching all tokens between {} and {}", Utc::now(),start_timestamp, end_timestamp);
-    let factory_address = Address::from_str(FACTORY_ADDRESS)?;
+    let factory_address = deployment.factory_address;
     let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
-    let mut logs = Vec::new();
-    let mut current_block_number = start_block_number;
-    while current_block_number <= end_block_number {
-        let next_block_number = min(current_block_number + BATCH_SIZE, end_block_number);
-        let filter = Filter::new()
-            .address(factory_address)
-            .topic0(H256::from_str(POOL_CREATED_SIGNATURE).unwrap())
-            .from_block(current_block_number)
-            .to_block(next_block_number);
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
+    let start_block_number = max(start_block_number, U64::from(deployment.deployment_block));
+    let filter = Filter::new()
+        .address(factory_address)
+        .topic0(H256::from_str(POOL_CREATED_SIGNATURE).unwrap());
+    let logs = get_logs_in_batches(provider.clone(), filter, start_block_number, end_block_number, max_concurrent_batches).await?;
 
     let mut token_addresses = HashSet::new();
     for log in logs {
@@ -809,29 +1951,14 @@ async fn get_recent_pool_events(
     provider: Arc<Provider<Http>>,
     pool_address: Address,
     start_timestamp: u64,
+    event_filter: PoolEventFilter,
+    max_concurrent_batches: usize,
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
     println!("{} | Fetching recent pool events for pool {} starting from timestamp {}", Utc::now(), pool_address, start_timestamp);
     let average_block_time = get_average_block_time(provider.clone()).await?;
     let start_block_number = get_block_number_from_timestamp(provider.clone(), start_timestamp, average_block_time).await?;
     let end_block_number = provider.get_block_number().await?;
-    let mut current_block_number = start_block_number;
-    let mut logs = Vec::new();
-    while current_block_number <= end_block_number {
-        let next_block_number = min(current_block_number + BATCH_SIZE, end_block_number);
-        let filter = Filter::new()
-            .address(pool_address)
-            .from_block(current_block_number)
-            .to_block(next_block_number)
-            .topic0(vec![
-                H256::from_str(SWAP_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(MINT_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(BURN_EVENT_SIGNATURE).unwrap(),
-                H256::from_str(COLLECT_EVENT_SIGNATURE).unwrap(),
-            ]);
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
+    let logs = get_pool_logs(provider.clone(), Filter::new().address(pool_address), start_block_number, end_block_number, &event_filter, max_concurrent_batches).await?;
     let events = serialize_logs(logs, provider.clone(), Arc::new(Mutex::new(HashMap::new()))).await?;
     println!("{} | Completed fetching recent pool events for pool {} starting from timestamp {}", Utc::now(), pool_address, start_timestamp);
     Ok(events)
@@ -862,6 +1989,7 @@ async fn get_pool_price_ratios(
     end_timestamp: u64,
     interval: u64,
     block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    max_concurrent_batches: usize,
 ) -> Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>> {
     let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
 
@@ -872,97 +2000,44 @@ async fn get_pool_price_ratios(
 
     let (_, _, token0_decimals) = get_token_info(provider.clone(), token0, token_abis.clone()).await?;
     let (_, _, token1_decimals) = get_token_info(provider.clone(), token1, token_abis.clone()).await?;
-    let mut logs = Vec::new();
-    let mut current_block_number = start_block_number;
-    while current_block_number <= end_block_number {
-        let next_block_number = min(current_block_number + BATCH_SIZE, end_block_number);
-        let filter = Filter::new()
-            .address(pool_address)
-            .from_block(current_block_number)
-            .to_block(next_block_number)
-            .topic0(H256::from_str(SWAP_EVENT_SIGNATURE).unwrap());
-        let block_logs = provider.get_logs(&filter).await?;
-        logs.extend(block_logs);
-        current_block_number = next_block_number + 1;
-    }
+    let logs = get_swap_logs(provider.clone(), pool_address, start_block_number, end_block_number, max_concurrent_batches).await?;
 
-    let mut price_ratios = HashMap::new();
+    let token0_decimals = token0_decimals.as_u64().unwrap() as u32;
+    let token1_decimals = token1_decimals.as_u64().unwrap() as u32;
+    // last sqrt price of each interval, None for intervals without swaps
+    let mut sqrt_prices: HashMap<u64, Option<U256>> = HashMap::new();
     // initialize the price ratios with the timestamps between start_timestamp and end_timestamp
-    let mut timestamp = (start_timestamp + interval) / interval * interval;
+    let mut timestamp = interval_end_timestamp(start_timestamp, interval);
     while timestamp <= end_timestamp {
-        price_ratios.insert(timestamp, 0.0);
+        sqrt_prices.insert(timestamp, None);
         timestamp = timestamp + interval;
     }
     for log in logs {
-        let raw_log = RawLog {
-            topics: log.topics.clone(),
-            data: log.data.to_vec(),
-        };
-        let timestamp = {
-            let mut cache = block_cache.lock().await;
-            if let Some(&cached_timestamp) = cache.get(&log.block_number.unwrap().as_u64()) {
-                cached_timestamp
-            } else {
-                let block = provider.get_block(log.block_number.unwrap()).await?.ok_or("Block not found")?;
-                let timestamp = block.timestamp.as_u64();
-                cache.insert(log.block_number.unwrap().as_u64(), timestamp);
-                timestamp
-            }
-        };
-        let aggregated_timestamp = (timestamp + interval) / interval * interval;
-        if log.topics[0] == H256::from_str(SWAP_EVENT_SIGNATURE).unwrap() {
-            let swap_event = <SwapEvent as EthLogDecode>::decode_log(&raw_log)?;
-            let sqrt_price = ( swap_event.sqrt_price_x96 / 2u128.pow(96) ).as_u128() as f64;
-            let token0_decimals = token0_decimals.as_u64().unwrap();
-            let token1_decimals = token1_decimals.as_u64().unwrap();
-            let price_ratio = sqrt_price * sqrt_price * 10u128.pow(token0_decimals as u32) as f64 / 10u128.pow(token1_decimals as u32) as f64;
-            
-            price_ratios.insert(aggregated_timestamp, price_ratio);
-        }
+        let timestamp = get_block_timestamp(provider.clone(), block_cache.clone(), log.block_number.unwrap().as_u64()).await?;
+        let (sqrt_price_x96, _, _) = decode_swap(&log)?;
+        sqrt_prices.insert(interval_end_timestamp(timestamp, interval), Some(sqrt_price_x96));
     }
-    let mut result: Vec<Value> = price_ratios.iter().map(|(timestamp, price_ratio)| {
-        serde_json::json!({
-            "timestamp": timestamp,
-            "price_ratio": price_ratio,
-        })
-    }).collect();
-    result.sort_by(|a, b| a["timestamp"].as_u64().cmp(&b["timestamp"].as_u64()));
-    let mut current_price_ratio = 0.0;
-    if result.len() > 0 {
-        if result[0]["price_ratio"].as_f64().unwrap() == 0.0 {
-            let filter = Filter::new()
-            .address(pool_address)
-            .from_block(start_block_number - BATCH_SIZE)
-            .to_block(start_block_number)
-            .topic0(H256::from_str(SWAP_EVENT_SIGNATURE).unwrap());
-            let block_logs = provider.get_logs(&filter).await?;
-            for block_log in block_logs {
-                let raw_log = RawLog {
-                    topics: block_log.topics.clone(),
-                    data: block_log.data.to_vec(),
-                };
-                if block_log.topics[0] == H256::from_str(SWAP_EVENT_SIGNATURE).unwrap() {
-                    let swap_event = <SwapEvent as EthLogDecode>::decode_log(&raw_log)?;
-                    let sqrt_price = ( swap_event.sqrt_price_x96 / 2u128.pow(96) ).as_u128() as f64;
-                    let token0_decimals = token0_decimals.as_u64().unwrap();
-                    let token1_decimals = token1_decimals.as_u64().unwrap();
-                    let price_ratio = sqrt_price * sqrt_price * 10u128.pow(token0_decimals as u32) as f64 / 10u128.pow(token1_decimals as u32) as f64;
-                    current_price_ratio = price_ratio;
-                    break;
-                }
-            }
-        }
+    let mut sqrt_prices: Vec<(u64, Option<U256>)> = sqrt_prices.into_iter().collect();
+    sqrt_prices.sort_by_key(|(timestamp, _)| *timestamp);
+    let mut current_sqrt_price: Option<U256> = None;
+    if sqrt_prices.first().is_some_and(|(_, sqrt_price)| sqrt_price.is_none()) {
+        current_sqrt_price = get_previous_sqrt_price(provider.clone(), pool_address, start_block_number, max_concurrent_batches).await?;
     }
-    for item in result.iter_mut() {
-        if item["price_ratio"].as_f64().unwrap() == 0.0 {
-            item.as_object_mut().unwrap().insert("price_ratio".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(current_price_ratio).unwrap()));
+    // intervals without swaps carry the previous price forward, and are "0" until the first known price
+    let mut result = Vec::new();
+    for (timestamp, sqrt_price) in sqrt_prices {
+        if sqrt_price.is_some() {
+            current_sqrt_price = sqrt_price;
         }
-        current_price_ratio = item["price_ratio"].as_f64().unwrap();
-    }
-    // change price_ratio type to string
-    for item in result.iter_mut() {
-        let price_ratio = item["price_ratio"].as_f64().unwrap().to_string();
-        item.as_object_mut().unwrap().insert("price_ratio".to_string(), serde_json::Value::String(price_ratio));
+        let price = current_sqrt_price
+            .map(|sqrt_price| Price::from_sqrt_price_x96(sqrt_price).adjust_decimals(token0_decimals, token1_decimals))
+            .unwrap_or_else(|| Price::new(0u8.into(), 1u8.into()));
+        let (price_ratio, inverse_price_ratio) = price_strings(&price);
+        result.push(serde_json::json!({
+            "timestamp": timestamp,
+            "price_ratio": price_ratio,
+            "inverse_price_ratio": inverse_price_ratio,
+        }));
     }
     Ok(result)
 
@@ -972,6 +2047,7 @@ async fn get_pool_price_ratios(
 #[pymodule]
 fn uniswap_fetcher_rs(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<UniswapFetcher>()?;
+    m.add_class::<event_stream::PoolEventStream>()?;
     Ok(())
 }
 
@@ -980,6 +2056,7 @@ fn uniswap_fetcher_rs(_py: Python, m: &PyModule) -> PyResult<()> {
 mod tests {
 
     use super::*;
+    use deployments::{get_deployment, get_v2_deployment};
     use chrono::{NaiveDateTime, Utc, TimeZone};
 
     #[tokio::test]
@@ -1004,7 +2081,7 @@ mod tests {
         let block_cache = Arc::new(Mutex::new(HashMap::new()));
         let token_pairs = vec![(token0.to_string(), token1.to_string(), fee)];
 
-        let result = fetch_pool_data(provider, block_cache, token_pairs, first_timestamp, second_timestamp).await;
+        let result = fetch_pool_data(provider, block_cache, &get_deployment("uniswap_v3", 1).unwrap(), token_pairs, first_timestamp, second_timestamp, PoolEventFilter::default(), false, MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
     }
 
@@ -1021,8 +2098,28 @@ mod tests {
         let block_cache = Arc::new(Mutex::new(HashMap::new()));
         let token_pairs = vec![(token0.to_string(), token1.to_string(), fee)];
 
-        let result = get_pool_events_by_token_pairs(provider, block_cache, token_pairs, U64::from(from_block), U64::from(to_block)).await;
+        let result = get_pool_events_by_token_pairs(provider, block_cache, &get_deployment("uniswap_v3", 1).unwrap(), token_pairs, U64::from(from_block), U64::from(to_block), PoolEventFilter::default(), false, MAX_CONCURRENT_BATCHES).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pool_addresses() {
+        let usdc = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+        let block_number = 17000000;
+        let rpc_url = "http://localhost:8545";
+
+        let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
+        let token_pairs = vec![
+            (usdc.to_string(), weth.to_string(), 500),
+            (usdc.to_string(), weth.to_string(), 2500),
+            (usdc.to_string(), usdc.to_string(), 500),
+        ];
+
+        let result = resolve_pool_addresses(provider, &get_deployment("uniswap_v3", 1).unwrap(), token_pairs, U64::from(block_number), false, MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
+        let statuses: Vec<&str> = result.unwrap().iter().map(|pool| pool.status).collect();
+        assert_eq!(statuses, vec!["found", "invalid_fee_tier", "missing"]);
     }
 
     #[tokio::test]
@@ -1036,7 +2133,7 @@ mod tests {
         let block_cache = Arc::new(Mutex::new(HashMap::new()));
         let pool_addresses: Vec<Address> = pool_addresses.iter().map(|address| Address::from_str(address).unwrap()).collect();
 
-        let result = get_pool_events_by_pool_addresses(provider, block_cache, pool_addresses, U64::from(from_block), U64::from(to_block)).await;
+        let result = get_pool_events_by_pool_addresses(provider, block_cache, pool_addresses, U64::from(from_block), U64::from(to_block), PoolEventFilter::default(), MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
     }
 
@@ -1050,8 +2147,14 @@ mod tests {
         let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
         let pool_address = Address::from_str(pool_address).unwrap();
 
-        let result = get_signals_by_pool_address(provider, pool_address, timestamp, interval).await;
-        assert!(result.is_ok());
+        let block_cache = Arc::new(Mutex::new(HashMap::new()));
+
+        let result = signals::get_signals_by_pool_address(provider.clone(), block_cache.clone(), pool_address, timestamp, interval, None, MAX_CONCURRENT_BATCHES).await;
+        assert_eq!(result.unwrap().len(), 1);
+
+        // An hour of 5 minute windows
+        let result = signals::get_signals_by_pool_address(provider, block_cache, pool_address, timestamp, interval, Some(timestamp + 3599), MAX_CONCURRENT_BATCHES).await;
+        assert_eq!(result.unwrap().len(), 12);
     }
 
     #[tokio::test]
@@ -1075,10 +2178,10 @@ mod tests {
         let rpc_url = "http://localhost:8545";
 
         let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
-        let factory_address = Address::from_str(FACTORY_ADDRESS).unwrap();
+        let deployment = get_deployment("uniswap_v3", 1).unwrap();
         let token_info_cache = Arc::new(Mutex::new(HashMap::new()));
 
-        let result = get_pool_created_events_between_two_timestamps(provider, token_info_cache.clone(), factory_address, start_timestamp, end_timestamp).await;
+        let result = get_pool_created_events_between_two_timestamps(provider, token_info_cache.clone(), &deployment, start_timestamp, end_timestamp, MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
     }
     #[tokio::test]
@@ -1108,7 +2211,7 @@ mod tests {
 
         let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
 
-        let result = get_all_tokens(provider, start_timestamp, end_timestamp).await;
+        let result = get_all_tokens(provider, &get_deployment("uniswap_v3", 1).unwrap(), start_timestamp, end_timestamp, MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
     }
 
@@ -1121,7 +2224,7 @@ mod tests {
         let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
         let pool_address = Address::from_str(pool_address).unwrap();
 
-        let result = get_recent_pool_events(provider, pool_address, timestamp).await;
+        let result = get_recent_pool_events(provider, pool_address, timestamp, PoolEventFilter::default(), MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
     }
 
@@ -1133,7 +2236,7 @@ mod tests {
 
         let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
 
-        let result = get_all_token_pairs(provider, start_timestamp, end_timestamp).await;
+        let result = get_all_token_pairs(provider, &get_deployment("uniswap_v3", 1).unwrap(), start_timestamp, end_timestamp, MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
     }
 
@@ -1148,6 +2251,616 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_decode_flash_event() {
+        let sender = Address::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let recipient = Address::from_str("0x0000000000000000000000000000000000000002").unwrap();
+        let log = Log {
+            topics: vec![
+                H256::from_str(FLASH_EVENT_SIGNATURE).unwrap(),
+                H256::from(sender),
+                H256::from(recipient),
+            ],
+            data: ethers::abi::encode(&[
+                ethers::abi::Token::Uint(U256::from(1000)),
+                ethers::abi::Token::Uint(U256::from(2000)),
+                ethers::abi::Token::Uint(U256::from(3)),
+                ethers::abi::Token::Uint(U256::from(6)),
+            ]).into(),
+            transaction_hash: Some(H256::zero()),
+            block_number: Some(U64::from(12376933)),
+            ..Default::default()
+        };
+
+        let (event, _, block_number) = decode_uniswap_event(&log).unwrap();
+        assert_eq!(block_number, 12376933);
+        match event {
+            UniswapEvent::Flash(flash) => {
+                assert_eq!(flash.sender, sender);
+                assert_eq!(flash.recipient, recipient);
+                assert_eq!(flash.amount0, U256::from(1000));
+                assert_eq!(flash.paid1, U256::from(6));
+            },
+            _ => panic!("expected flash event"),
+        }
+    }
+
+    #[test]
+    fn test_get_pool_event_signatures() {
+        let event_types = vec!["initialize".to_string(), "set_fee_protocol".to_string()];
+        let signatures = get_pool_event_signatures(&event_types).unwrap();
+        assert_eq!(signatures, vec![
+            H256::from_str(INITIALIZE_EVENT_SIGNATURE).unwrap(),
+            H256::from_str(SET_FEE_PROTOCOL_EVENT_SIGNATURE).unwrap(),
+        ]);
+        assert!(get_pool_event_signatures(&["sync".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_get_pool_event_filters() {
+        let owner = Address::from_str("0xc36442b4a4522e871399cd717abca847e3e3aa8e").unwrap();
+
+        let filters = get_pool_event_filters(Filter::new(), &PoolEventFilter::default()).unwrap();
+        assert_eq!(filters.len(), 1);
+
+        let event_filter = PoolEventFilter { owner: vec![owner], tick_lower: vec![-887220], ..Default::default() };
+        let filters = get_pool_event_filters(Filter::new(), &event_filter).unwrap();
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].topics[1], Some(ethers::types::ValueOrArray::Array(vec![Some(H256::from(owner))])));
+        assert_eq!(tick_to_topic(-887220), H256::from_str("0xfffffffffffffffffffffffffffffffffffffffffffffffffffffffffff2764c").unwrap());
+
+        let event_filter = PoolEventFilter { owner: vec![owner], sender: vec![owner], ..Default::default() };
+        assert!(get_pool_event_filters(Filter::new(), &event_filter).is_err());
+    }
+
+    #[test]
+    fn test_compute_pool_address() {
+        let usdc = Address::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").unwrap();
+        let weth = Address::from_str("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap();
+        let deployment = get_deployment("uniswap_v3", 1).unwrap();
+        let factory_address = deployment.pool_deployer;
+        let init_code_hash = deployment.init_code_hash;
+
+        let pool_address = compute_pool_address(factory_address, usdc, weth, 500, init_code_hash);
+        assert_eq!(pool_address, Address::from_str("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").unwrap());
+        // Token order does not matter
+        assert_eq!(compute_pool_address(factory_address, weth, usdc, 500, init_code_hash), pool_address);
+        assert_eq!(
+            compute_pool_address(factory_address, usdc, weth, 3000, init_code_hash),
+            Address::from_str("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8").unwrap()
+        );
+
+        // PancakeSwap V3 pools are deployed by its pool deployer rather than the factory
+        let deployment = get_deployment("pancakeswap_v3", 56).unwrap();
+        let wbnb = Address::from_str("0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c").unwrap();
+        let busd = Address::from_str("0xe9e7cea3dedca5984780bafc599bd69add087d56").unwrap();
+        assert_eq!(
+            compute_pool_address(deployment.pool_deployer, wbnb, busd, 500, deployment.init_code_hash),
+            Address::from_str("0x85faac652b707fdf6907ef726751087f9e0b6687").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_deployments() {
+        let optimism = deployments::resolve_deployments("uniswap_v3", 10).unwrap();
+        assert_eq!(optimism.deployment.chain_id, 10);
+        assert!(optimism.v2_deployment.is_none());
+
+        // A local fork keeps its chain id but uses the mainnet addresses, other unregistered chains have no deployments
+        let fork = deployments::resolve_deployments("pancakeswap_v3", 31337).unwrap();
+        let mainnet = deployments::resolve_deployments("pancakeswap_v3", 1).unwrap();
+        assert_eq!(fork.deployment.chain_id, 31337);
+        assert_eq!(fork.deployment.pool_deployer, mainnet.deployment.pool_deployer);
+        assert_eq!(fork.deployment.init_code_hash, mainnet.deployment.init_code_hash);
+        assert!(fork.v2_deployment.is_some() && fork.v4_deployment.is_some());
+        assert!(deployments::resolve_deployments("uniswap_v3", 43114).is_none());
+        assert!(deployments::resolve_deployments("sushiswap_v3", 10).is_none());
+
+        assert!(deployments::resolve_deployments("unknown", 1).is_none());
+    }
+
+    #[test]
+    fn test_compute_v2_pair_address() {
+        let usdc = Address::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").unwrap();
+        let weth = Address::from_str("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap();
+        let deployment = get_v2_deployment(1).unwrap();
+        let pair_address = v2::compute_v2_pair_address(deployment.factory_address, usdc, weth, deployment.init_code_hash);
+        assert_eq!(pair_address, Address::from_str("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc").unwrap());
+        assert_eq!(v2::compute_v2_pair_address(deployment.factory_address, weth, usdc, deployment.init_code_hash), pair_address);
+        assert!(get_v2_deployment(56).is_none());
+    }
+
+    #[test]
+    fn test_decode_v2_swap_event() {
+        let sender = Address::from_str("0x7a250d5630b4cf539739df2c5dacb4c659f2488d").unwrap();
+        let to = Address::from_str("0x0000000000000000000000000000000000000002").unwrap();
+        let log = Log {
+            topics: vec![
+                H256::from_str(v2::V2_SWAP_EVENT_SIGNATURE).unwrap(),
+                H256::from(sender),
+                H256::from(to),
+            ],
+            data: ethers::abi::encode(&[
+                ethers::abi::Token::Uint(U256::from(0)),
+                ethers::abi::Token::Uint(U256::from(1000)),
+                ethers::abi::Token::Uint(U256::from(2500)),
+                ethers::abi::Token::Uint(U256::from(0)),
+            ]).into(),
+            ..Default::default()
+        };
+
+        match v2::decode_v2_event(&log).unwrap() {
+            v2::UniswapV2Event::Swap(swap) => {
+                assert_eq!(swap.sender, sender);
+                assert_eq!(swap.to, to);
+                assert_eq!(swap.amount1_in, U256::from(1000));
+                assert_eq!(swap.amount0_out, U256::from(2500));
+            },
+            _ => panic!("expected V2 swap event"),
+        }
+    }
+
+    #[test]
+    fn test_v4_event_signatures() {
+        assert_eq!(v4::V4InitializeEvent::signature(), H256::from_str(v4::V4_INITIALIZE_EVENT_SIGNATURE).unwrap());
+        assert_eq!(v4::V4ModifyLiquidityEvent::signature(), H256::from_str(v4::V4_MODIFY_LIQUIDITY_EVENT_SIGNATURE).unwrap());
+        assert_eq!(v4::V4SwapEvent::signature(), H256::from_str(v4::V4_SWAP_EVENT_SIGNATURE).unwrap());
+        assert_eq!(v4::V4DonateEvent::signature(), H256::from_str(v4::V4_DONATE_EVENT_SIGNATURE).unwrap());
+    }
+
+    #[test]
+    fn test_decode_v4_swap_event() {
+        let usdc = Address::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").unwrap();
+        let pool_id = v4::compute_v4_pool_id(usdc, Address::zero(), 500, 10, Address::zero());
+        assert_eq!(pool_id, H256::from_str("0x21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27").unwrap());
+
+        let sender = Address::from_str("0x66a9893cc07d91d95644aedd05d03f95e1dba8af").unwrap();
+        let log = Log {
+            topics: vec![
+                H256::from_str(v4::V4_SWAP_EVENT_SIGNATURE).unwrap(),
+                pool_id,
+                H256::from(sender),
+            ],
+            data: ethers::abi::encode(&[
+                ethers::abi::Token::Int(I256::from(-1_000_000).into_raw()),
+                ethers::abi::Token::Int(I256::from(400_000_000_000_000u64).into_raw()),
+                ethers::abi::Token::Uint(U256::from(2).pow(U256::from(96))),
+                ethers::abi::Token::Uint(U256::from(10_000)),
+                ethers::abi::Token::Int(I256::from(-200_000).into_raw()),
+                ethers::abi::Token::Uint(U256::from(500)),
+            ]).into(),
+            ..Default::default()
+        };
+
+        match v4::decode_v4_event(&log).unwrap() {
+            v4::UniswapV4Event::Swap(swap) => {
+                assert_eq!(swap.id, pool_id);
+                assert_eq!(swap.sender, sender);
+                assert_eq!(swap.amount0, I256::from(-1_000_000));
+                assert_eq!(swap.tick, -200_000);
+                assert_eq!(swap.fee, 500);
+            },
+            _ => panic!("expected V4 swap event"),
+        }
+    }
+
+    #[test]
+    fn test_build_positions() {
+        let abi = positions::get_position_manager_abi();
+        let deployment = get_deployment("uniswap_v3", 1).unwrap();
+        let pool_address = Address::from_str("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").unwrap();
+        let wallet = Address::from_str("0x0000000000000000000000000000000000000abc").unwrap();
+        let transaction_hash = H256::from_low_u64_be(1);
+        let token_id = U256::from(42);
+        let liquidity = U256::from(1_000_000);
+        let amounts = [U256::from(500), U256::from(700)];
+
+        let mint = Log {
+            address: pool_address,
+            topics: vec![
+                H256::from_str(MINT_EVENT_SIGNATURE).unwrap(),
+                H256::from(deployment.position_manager),
+                tick_to_topic(-887220),
+                tick_to_topic(887220),
+            ],
+            data: ethers::abi::encode(&[
+                ethers::abi::Token::Address(deployment.position_manager),
+                ethers::abi::Token::Uint(liquidity),
+                ethers::abi::Token::Uint(amounts[0]),
+                ethers::abi::Token::Uint(amounts[1]),
+            ]).into(),
+            transaction_hash: Some(transaction_hash),
+            log_index: Some(U256::from(1)),
+            ..Default::default()
+        };
+        let transfer = Log {
+            address: deployment.position_manager,
+            topics: vec![
+                abi.event("Transfer").unwrap().signature(),
+                H256::zero(),
+                H256::from(wallet),
+                H256::from_low_u64_be(42),
+            ],
+            transaction_hash: Some(transaction_hash),
+            log_index: Some(U256::from(2)),
+            ..Default::default()
+        };
+        let increase_liquidity = Log {
+            address: deployment.position_manager,
+            topics: vec![abi.event("IncreaseLiquidity").unwrap().signature(), H256::from_low_u64_be(42)],
+            data: ethers::abi::encode(&[
+                ethers::abi::Token::Uint(liquidity),
+                ethers::abi::Token::Uint(amounts[0]),
+                ethers::abi::Token::Uint(amounts[1]),
+            ]).into(),
+            transaction_hash: Some(transaction_hash),
+            log_index: Some(U256::from(3)),
+            ..Default::default()
+        };
+
+        let manager_events: Vec<_> = [transfer, increase_liquidity].into_iter()
+            .map(|log| { let event = positions::decode_position_manager_event(&abi, &log).unwrap(); (log, event) })
+            .collect();
+        let positions = positions::build_positions(&manager_events, &[mint]).unwrap();
+        let position = &positions[&token_id];
+        assert_eq!(position.owner, Some(wallet));
+        assert_eq!(position.pool_address, Some(pool_address));
+        assert_eq!(position.tick_lower, Some(-887220));
+        assert_eq!(position.tick_upper, Some(887220));
+        assert_eq!(position.liquidity_added, liquidity);
+        assert!(!position.burned);
+    }
+
+    #[test]
+    fn test_decode_abi_event() {
+        let abi: Abi = serde_json::from_str(include_str!("contracts/erc20_abi.json")).unwrap();
+        let events = abi_events::select_events(&abi, &["Transfer".to_string()]).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(abi_events::select_events(&abi, &["Swap".to_string()]).is_err());
+        let events: HashMap<H256, ethers::abi::Event> = events.into_iter().map(|event| (event.signature(), event)).collect();
+
+        let from = Address::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let to = Address::from_str("0x0000000000000000000000000000000000000002").unwrap();
+        let log = Log {
+            topics: vec![
+                H256::from_str("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef").unwrap(),
+                H256::from(from),
+                H256::from(to),
+            ],
+            data: ethers::abi::encode(&[ethers::abi::Token::Uint(U256::from(1000))]).into(),
+            ..Default::default()
+        };
+        let event = abi_events::decode_abi_event(&events, &log).unwrap();
+        assert_eq!(event["type"], "Transfer");
+        let data = event["data"].as_object().unwrap();
+        assert_eq!(data["from"], serde_json::json!(from));
+        assert_eq!(data["to"], serde_json::json!(to));
+        assert_eq!(data["value"], serde_json::json!(U256::from(1000)));
+    }
+
+    #[tokio::test]
+    async fn test_get_pool_state() {
+        let pool_address = Address::from_str("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").unwrap();
+        let rpc_url = "http://localhost:8545";
+
+        let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
+
+        let result = pool_state::get_pool_state(provider, pool_address, Some(U64::from(12376933))).await;
+        assert!(result.is_ok());
+        let state = result.unwrap();
+        assert_eq!(state.block_number, 12376933);
+        assert_eq!(state.fee, 500);
+    }
+
+    #[test]
+    fn test_tick_bitmap() {
+        assert_eq!(pool_state::tick_bitmap_word_range(1), (-3466, 3465));
+        assert_eq!(pool_state::tick_bitmap_word_range(60), (-58, 57));
+
+        let word = (U256::one() << 3) | (U256::one() << 200);
+        assert_eq!(pool_state::initialized_ticks_in_word(-1, word, 10), vec![(-256 + 3) * 10, (-256 + 200) * 10]);
+
+        let ticks = pool_state::accumulate_liquidity(&[
+            (-600, I256::from(1000), U256::from(1000)),
+            (0, I256::from(500), U256::from(500)),
+            (600, I256::from(-1500), U256::from(1500)),
+        ]);
+        assert_eq!(ticks.iter().map(|tick| tick.liquidity).collect::<Vec<_>>(), vec![U256::from(1000), U256::from(1500), U256::zero()]);
+    }
+
+    #[test]
+    fn test_liquidity_replay() {
+        let pool_address = Address::from_str("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").unwrap();
+        let mint = |tick_lower: i32, tick_upper: i32, amount: u64| MintEvent {
+            sender: Address::zero(),
+            owner: Address::zero(),
+            tick_lower,
+            tick_upper,
+            amount: U256::from(amount),
+            amount0: U256::zero(),
+            amount1: U256::zero(),
+        };
+        let burn = |amount: u64| BurnEvent { owner: Address::zero(), tick_lower: -600, tick_upper: 600, amount: U256::from(amount), amount0: U256::zero(), amount1: U256::zero() };
+
+        let mut replay = liquidity_replay::LiquidityReplay::new(pool_address);
+        replay.apply_event(100, &UniswapEvent::Mint(mint(-600, 600, 1000)));
+        replay.apply_event(101, &UniswapEvent::Mint(mint(0, 1200, 500)));
+        replay.block_number = Some(101);
+        // Resume from a serialized checkpoint; events of blocks it already covers are skipped
+        let mut replay: liquidity_replay::LiquidityReplay = serde_json::from_str(&serde_json::to_string(&replay).unwrap()).unwrap();
+        replay.apply_event(101, &UniswapEvent::Mint(mint(0, 1200, 500)));
+        replay.apply_event(102, &UniswapEvent::Burn(burn(400)));
+        replay.block_number = Some(102);
+
+        let ticks = replay.tick_liquidity();
+        assert_eq!(ticks.iter().map(|tick| tick.tick).collect::<Vec<_>>(), vec![-600, 0, 600, 1200]);
+        assert_eq!(ticks[0].liquidity_net, I256::from(600));
+        assert_eq!(ticks[1].liquidity, U256::from(1100));
+        assert_eq!(ticks[2].liquidity_gross, U256::from(600));
+        assert_eq!(ticks[3].liquidity, U256::zero());
+
+        // Burning the rest uninitializes the ticks
+        replay.apply_event(103, &UniswapEvent::Burn(burn(600)));
+        assert_eq!(replay.ticks.keys().cloned().collect::<Vec<_>>(), vec![0, 1200]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_liquidity_matches_tick_map() {
+        let pool_address = Address::from_str("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8").unwrap();
+        let block_number = U64::from(12376933);
+        let rpc_url = "http://localhost:8545";
+        let deployment = get_deployment("uniswap_v3", 1).unwrap();
+
+        let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
+
+        // Replay in two steps to go through a checkpoint
+        let replay = liquidity_replay::replay_liquidity(provider.clone(), &deployment, pool_address, block_number - 1000, None, MAX_CONCURRENT_BATCHES).await.unwrap();
+        let replay = liquidity_replay::replay_liquidity(provider.clone(), &deployment, pool_address, block_number, Some(replay), MAX_CONCURRENT_BATCHES).await.unwrap();
+        let tick_map = pool_state::get_tick_map(provider, pool_address, Some(block_number)).await.unwrap();
+        assert!(replay.mismatched_ticks(&tick_map).is_empty());
+    }
+
+    #[test]
+    fn test_build_candles() {
+        let swap = |timestamp: u64, sqrt_price_shift: usize, amount0: i64, amount1: i64| candles::CandleSwap {
+            timestamp,
+            sqrt_price_x96: U256::one() << sqrt_price_shift,
+            amount0: I256::from(amount0),
+            amount1: I256::from(amount1),
+        };
+        // Raw prices 1, 4 and 0.25 between tokens without decimals
+        let swaps = vec![
+            swap(990, 96, 10, -10),
+            swap(1010, 97, -1, 4),
+            swap(1020, 95, 8, -2),
+            swap(1030, 96, -3, 3),
+            swap(1250, 97, 1, -4),
+        ];
+        let candles = candles::build_candles(&swaps, 1000, 1400, 100, None, 0, 0);
+        assert_eq!(candles.len(), 5);
+
+        assert_eq!(candles[0]["timestamp"], 1100);
+        assert_eq!(candles[0]["open"], "4");
+        assert_eq!(candles[0]["high"], "4");
+        assert_eq!(candles[0]["low"], "0.25");
+        assert_eq!(candles[0]["close"], "1");
+        assert_eq!(candles[0]["swap_count"], 3);
+        assert_eq!(candles[0]["volume0"], "12");
+        assert_eq!(candles[0]["volume1"], "9");
+        assert_eq!(candles[0]["vwap"], "0.75");
+
+        // Empty intervals repeat the previous close
+        assert_eq!(candles[1]["timestamp"], 1200);
+        assert_eq!(candles[1]["open"], "1");
+        assert_eq!(candles[1]["close"], "1");
+        assert_eq!(candles[1]["swap_count"], 0);
+        assert_eq!(candles[1]["volume0"], "0");
+        assert_eq!(candles[1]["vwap"], Value::Null);
+        assert_eq!(candles[2]["close"], "4");
+        assert_eq!(candles[3]["open"], "4");
+        // The last candle contains the end timestamp, although its interval closes after it
+        assert_eq!(candles[4]["timestamp"], 1500);
+        assert_eq!(candles[4]["close"], "4");
+
+        // Volumes and prices are adjusted for the token decimals, and a series without a known price starts at null
+        let candles = candles::build_candles(&[swap(1150, 96, 1_000_000, -1_000_000_000_000)], 1000, 1200, 100, None, 6, 18);
+        assert_eq!(candles[0]["open"], Value::Null);
+        assert_eq!(candles[0]["close"], Value::Null);
+        assert_eq!(candles[0]["vwap"], Value::Null);
+        assert_eq!(candles[1]["close"], "0.000000000001");
+        assert_eq!(candles[1]["volume0"], "1");
+        assert_eq!(candles[1]["volume1"], "0.000001");
+        assert_eq!(candles[1]["vwap"], "0.000001");
+    }
+
+    #[test]
+    fn test_build_signals() {
+        let swap = |sqrt_price_shift: usize, amount0: i64, amount1: i64, liquidity: u64, tick: i32| UniswapEvent::Swap(SwapEvent {
+            sender: Address::zero(),
+            to: Address::zero(),
+            amount0: I256::from(amount0),
+            amount1: I256::from(amount1),
+            sqrt_price_x96: U256::one() << sqrt_price_shift,
+            liquidity: U256::from(liquidity),
+            tick,
+        });
+        let owner = Address::from_low_u64_be(1);
+        let mint = |tick_lower: i32, tick_upper: i32, amount: u64| UniswapEvent::Mint(MintEvent {
+            sender: owner,
+            owner,
+            tick_lower,
+            tick_upper,
+            amount: U256::from(amount),
+            amount0: U256::zero(),
+            amount1: U256::zero(),
+        });
+        let burn = |amount: u64, amount0: u64, amount1: u64| UniswapEvent::Burn(BurnEvent {
+            owner,
+            tick_lower: -60,
+            tick_upper: 60,
+            amount: U256::from(amount),
+            amount0: U256::from(amount0),
+            amount1: U256::from(amount1),
+        });
+        let collect = |amount0: u64, amount1: u64| UniswapEvent::Collect(CollectEvent {
+            owner,
+            recipient: owner,
+            tick_lower: -60,
+            tick_upper: 60,
+            amount0: U256::from(amount0),
+            amount1: U256::from(amount1),
+        });
+        let events = vec![
+            (1000, swap(97, -1_000_000, 4_000_000_000_000, 1000, 0)),
+            (1100, swap(95, 3_000_000, -750_000_000_000, 1000, 0)),
+            (1150, mint(-60, 60, 500)),
+            (1160, mint(60, 120, 200)),  // out of range
+            (1300, swap(96, 1_000_000, -1_000_000_000_000, 1500, 0)),
+            (1310, burn(300, 2_000_000, 0)),
+            (1320, collect(2_500_000, 1_000_000_000_000)),
+            (1400, swap(96, 1_000_000, -1_000_000_000_000, 1200, 0)),
+        ];
+        let signals = signals::build_signals(&events, 1000, 1399, 300, Some((U256::from(1000), 0)), 6, 18);
+        assert_eq!(signals.len(), 2);
+
+        assert_eq!(signals[0]["start_timestamp"], 1000);
+        assert_eq!(signals[0]["end_timestamp"], 1299);
+        assert_eq!(signals[0]["swap_count"], 2);
+        // Mean of the raw prices 4 and 0.25, adjusted for the decimals
+        assert_eq!(signals[0]["price"], "0.000000000002125");
+        // Volumes are kept per token, in whole tokens
+        assert_eq!(signals[0]["volume0"], "4");
+        assert_eq!(signals[0]["volume1"], "0.00000475");
+        // Only the in range mint changes the active liquidity
+        assert_eq!(signals[0]["liquidity_start"], "1000");
+        assert_eq!(signals[0]["liquidity_end"], "1500");
+        assert_eq!(signals[0]["liquidity_added"], "700");
+        assert_eq!(signals[0]["liquidity_delta"], "700");
+        assert_eq!(signals[0]["positions"].as_array().unwrap().len(), 2);
+
+        // The last window is cut at the end timestamp, later events are left out
+        assert_eq!(signals[1]["start_timestamp"], 1300);
+        assert_eq!(signals[1]["end_timestamp"], 1399);
+        assert_eq!(signals[1]["swap_count"], 1);
+        assert_eq!(signals[1]["liquidity_start"], "1500");
+        assert_eq!(signals[1]["liquidity_end"], "1200");
+        assert_eq!(signals[1]["liquidity_removed"], "300");
+        assert_eq!(signals[1]["liquidity_delta"], "-300");
+        // The collect first withdraws the 2 burned token0, the rest is fees
+        assert_eq!(signals[1]["fees0"], "0.5");
+        assert_eq!(signals[1]["fees1"], "0.000001");
+        let position = &signals[1]["positions"][0];
+        assert_eq!(position["tick_lower"], -60);
+        assert_eq!(position["liquidity_removed"], "300");
+        assert_eq!(position["fees0"], "0.5");
+
+        // Without a known state the absolute liquidity stays unknown until the first swap
+        let signals = signals::build_signals(&events, 1000, 1399, 300, None, 6, 18);
+        assert_eq!(signals[0]["liquidity_start"], Value::Null);
+        assert_eq!(signals[0]["liquidity_end"], "1500");
+
+        // A window without swaps has no price
+        let signals = signals::build_signals(&[], 1000, 1099, 300, None, 6, 18);
+        assert_eq!(signals[0]["swap_count"], 0);
+        assert_eq!(signals[0]["price"], Value::Null);
+    }
+
+    #[test]
+    fn test_twap_math() {
+        assert_eq!(pool_state::arithmetic_mean_tick(2_000_000, 600), 3333);
+        assert_eq!(pool_state::arithmetic_mean_tick(-2_000_000, 600), -3334);
+        assert_eq!(pool_state::arithmetic_mean_tick(-1_200, 600), -2);
+
+        // A constant liquidity L accrues window * 2^128 / L seconds per liquidity
+        let liquidity = U256::from(1_000_000_000_000u64);
+        let seconds_per_liquidity_delta = (U256::from(600) << 128) / liquidity;
+        let mean_liquidity = pool_state::harmonic_mean_liquidity(seconds_per_liquidity_delta, 600).unwrap();
+        assert!(mean_liquidity.abs_diff(liquidity) <= U256::one());
+        assert_eq!(pool_state::harmonic_mean_liquidity(U256::zero(), 600), None);
+
+        // The uint160 seconds per liquidity accumulator wraps around between the two observations
+        let max_uint160 = (U256::one() << 160) - 1;
+        assert_eq!(pool_state::seconds_per_liquidity_delta(U256::from(100), U256::from(250)), U256::from(150));
+        assert_eq!(pool_state::seconds_per_liquidity_delta(max_uint160 - 99, U256::from(50)), U256::from(150));
+    }
+
+    #[test]
+    fn test_price_math() {
+        assert_eq!(price_math::get_sqrt_ratio_at_tick(0).unwrap(), U256::one() << 96);
+        assert_eq!(price_math::get_sqrt_ratio_at_tick(price_math::MIN_TICK).unwrap(), U256::from_dec_str("4295128739").unwrap());
+        assert_eq!(
+            price_math::get_sqrt_ratio_at_tick(price_math::MAX_TICK).unwrap(),
+            U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap()
+        );
+        assert!(price_math::get_sqrt_ratio_at_tick(price_math::MAX_TICK + 1).is_err());
+        for tick in [price_math::MIN_TICK, -200000, -1, 0, 1, 60, 200000, price_math::MAX_TICK - 1] {
+            let sqrt_ratio = price_math::get_sqrt_ratio_at_tick(tick).unwrap();
+            assert_eq!(price_math::get_tick_at_sqrt_ratio(sqrt_ratio).unwrap(), tick);
+            assert_eq!(price_math::get_tick_at_sqrt_ratio(sqrt_ratio + 1).unwrap(), tick);
+            if tick > price_math::MIN_TICK {
+                assert_eq!(price_math::get_tick_at_sqrt_ratio(sqrt_ratio - 1).unwrap(), tick - 1);
+            }
+        }
+
+        // Prices below 1 no longer collapse to 0, and prices with a finite expansion are exact
+        assert_eq!(Price::from_sqrt_price_x96(U256::one() << 95).to_decimal_string(), "0.25");
+        assert_eq!(Price::from_sqrt_price_x96(U256::one() << 96).adjust_decimals(6, 18).to_decimal_string(), "0.000000000001");
+        assert_eq!(price_strings(&Price::from_sqrt_price_x96(U256::one() << 97)), ("4".to_string(), "0.25".to_string()));
+        assert_eq!(price_strings(&Price::new(0u8.into(), 1u8.into())), ("0".to_string(), "0".to_string()));
+        // Other prices are truncated to 40 significant digits
+        assert_eq!(Price::new(2u8.into(), 3u8.into()).to_decimal_string(), format!("0.{}", "6".repeat(40)));
+        assert_eq!(Price::new(1u8.into(), 3000u32.into()).to_decimal_string(), format!("0.000{}", "3".repeat(40)));
+
+        // USDC/WETH at tick 200000 is about 2063.2 USDC per WETH
+        let price = Price::from_tick(200000).unwrap().adjust_decimals(6, 18);
+        let (_, inverse_price) = price_strings(&price);
+        assert!(inverse_price.starts_with("2063.21"));
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        fn missing_value() -> Value {
+            panic!("missing value")
+        }
+        let query: Query = Box::pin(async { Ok(missing_value()) });
+        assert_eq!(catch_panic(query).await.unwrap_err().to_string(), "Query panicked: missing value");
+        let query: Query = Box::pin(async { Ok(serde_json::json!(1)) });
+        assert_eq!(catch_panic(query).await.unwrap(), serde_json::json!(1));
+    }
+
+    #[test]
+    fn test_shared_runtime() {
+        // Every fetcher runs on the same runtime, which no fetcher owns
+        let runtime = shared_runtime().unwrap();
+        assert!(std::ptr::eq(runtime, shared_runtime().unwrap()));
+
+        // A query still completes once everything of the fetcher that started it is dropped
+        let provider = Arc::new(Provider::<Http>::try_from("http://127.0.0.1:1").unwrap());
+        let query: Query = {
+            let provider = provider.clone();
+            Box::pin(async move { Ok(serde_json::json!(provider.get_block_number().await.is_err())) })
+        };
+        drop(provider);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        runtime.spawn(async move {
+            let _ = sender.send(catch_panic(query).await.map_err(|e| e.to_string()));
+        });
+        let result = receiver.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        assert_eq!(result.unwrap(), serde_json::json!(true));
+    }
+
+    #[test]
+    fn test_block_windows() {
+        let windows = block_windows(U64::from(100), U64::from(25000));
+        assert_eq!(windows, vec![
+            (U64::from(100), U64::from(10100)),
+            (U64::from(10101), U64::from(20101)),
+            (U64::from(20102), U64::from(25000)),
+        ]);
+        assert_eq!(block_windows(U64::from(7), U64::from(7)), vec![(U64::from(7), U64::from(7))]);
+        assert!(block_windows(U64::from(8), U64::from(7)).is_empty());
+    }
+
     #[tokio::test]
     async fn test_get_recent_price_ratio() {
         let pool_address = "0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8";
@@ -1160,10 +2873,28 @@ mod tests {
         let provider = Arc::new(Provider::<Http>::try_from(rpc_url).unwrap());
         let pool_address = Address::from_str(pool_address).unwrap();
 
-        let result = get_pool_price_ratios(provider, pool_address, start_timestamp, end_timestamp, interval, block_cache).await;
+        let result = get_pool_price_ratios(provider, pool_address, start_timestamp, end_timestamp, interval, block_cache, MAX_CONCURRENT_BATCHES).await;
         assert!(result.is_ok());
         let values = result.unwrap();
         dbg!(values);
     }
 
+    #[tokio::test]
+    async fn test_get_pool_candles() {
+        let pool_address = Address::from_str("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8").unwrap();
+        let start_timestamp = 1733875210;
+        let end_timestamp = 1733877000;
+        let interval = 300; // 5-min in seconds
+        let provider = Arc::new(Provider::<Http>::try_from("http://localhost:8545").unwrap());
+        let block_cache = Arc::new(Mutex::new(HashMap::new()));
+
+        let candles = candles::get_pool_candles(provider, block_cache, pool_address, start_timestamp, end_timestamp, interval, MAX_CONCURRENT_BATCHES).await.unwrap();
+        assert_eq!(candles.len(), 6);
+        for candle in &candles {
+            let low: f64 = candle["low"].as_str().unwrap().parse().unwrap();
+            let high: f64 = candle["high"].as_str().unwrap().parse().unwrap();
+            assert!(low <= high);
+        }
+    }
+
 }
@@ -0,0 +1,124 @@
+use crate::deployments::Deployment;
+use crate::pool_state::{accumulate_liquidity, TickLiquidity, TickMap};
+use crate::{decode_uniswap_event, get_pool_logs, BurnEvent, MintEvent, PoolEventFilter, UniswapEvent};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Filter, I256, U256, U64};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickDelta {
+    pub liquidity_net: I256,
+    pub liquidity_gross: U256,
+}
+
+// Per-tick liquidity rebuilt from decoded Mint and Burn events. Serialized, it is the checkpoint a replay
+// is extended from; events are replayed a whole block at a time so the next replay starts at block_number + 1.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LiquidityReplay {
+    pub pool_address: Address,
+    pub block_number: Option<u64>,  // last block whose events were applied
+    pub ticks: BTreeMap<i32, TickDelta>,
+}
+
+impl LiquidityReplay {
+    pub fn new(pool_address: Address) -> Self {
+        LiquidityReplay { pool_address, ..Default::default() }
+    }
+
+    fn update_tick(&mut self, tick: i32, liquidity_net: I256, liquidity_gross: I256) {
+        let delta = self.ticks.entry(tick).or_default();
+        delta.liquidity_net += liquidity_net;
+        delta.liquidity_gross = (I256::from_raw(delta.liquidity_gross) + liquidity_gross).into_raw();
+        // A tick without liquidity referencing it is no longer initialized
+        if delta.liquidity_gross.is_zero() {
+            self.ticks.remove(&tick);
+        }
+    }
+
+    pub fn apply_mint(&mut self, mint: &MintEvent) {
+        let amount = I256::from_raw(mint.amount);
+        self.update_tick(mint.tick_lower, amount, amount);
+        self.update_tick(mint.tick_upper, -amount, amount);
+    }
+
+    pub fn apply_burn(&mut self, burn: &BurnEvent) {
+        let amount = I256::from_raw(burn.amount);
+        self.update_tick(burn.tick_lower, -amount, -amount);
+        self.update_tick(burn.tick_upper, amount, -amount);
+    }
+
+    // Apply a decoded pool event of `block_number`; events of blocks the checkpoint already covers are skipped
+    pub fn apply_event(&mut self, block_number: u64, event: &UniswapEvent) {
+        if self.block_number.is_some_and(|replayed| block_number <= replayed) {
+            return;
+        }
+        match event {
+            UniswapEvent::Mint(mint) => self.apply_mint(mint),
+            UniswapEvent::Burn(burn) => self.apply_burn(burn),
+            _ => {},
+        }
+    }
+
+    pub fn tick_liquidity(&self) -> Vec<TickLiquidity> {
+        let ticks: Vec<(i32, I256, U256)> = self.ticks.iter()
+            .map(|(tick, delta)| (*tick, delta.liquidity_net, delta.liquidity_gross))
+            .collect();
+        accumulate_liquidity(&ticks)
+    }
+
+    // Ticks whose replayed liquidity differs from the on-chain tick map, including ticks missing on either side
+    pub fn mismatched_ticks(&self, tick_map: &TickMap) -> Vec<i32> {
+        let mut on_chain: BTreeMap<i32, TickDelta> = tick_map.ticks.iter()
+            .map(|tick| (tick.tick, TickDelta { liquidity_net: tick.liquidity_net, liquidity_gross: tick.liquidity_gross }))
+            .collect();
+        let mut mismatched: Vec<i32> = self.ticks.iter()
+            .filter(|(tick, delta)| on_chain.remove(tick).as_ref() != Some(*delta))
+            .map(|(tick, _)| *tick)
+            .collect();
+        mismatched.extend(on_chain.keys());
+        mismatched.sort();
+        mismatched
+    }
+}
+
+// Replay the pool's Mint and Burn events up to `to_block`, extending `checkpoint` when given and starting
+// at the factory deployment otherwise. Needs only logs, no state reads. `to_block` must already be mined, since the
+// next replay from the checkpoint starts after it.
+pub async fn replay_liquidity(
+    provider: Arc<Provider<Http>>,
+    deployment: &Deployment,
+    pool_address: Address,
+    to_block: U64,
+    checkpoint: Option<LiquidityReplay>,
+    max_concurrent_batches: usize,
+) -> Result<LiquidityReplay, Box<dyn std::error::Error + Send + Sync>> {
+    let mut replay = match checkpoint {
+        Some(checkpoint) if checkpoint.pool_address != pool_address => {
+            return Err(format!("Checkpoint is for pool {:?}, not {:?}", checkpoint.pool_address, pool_address).into());
+        },
+        Some(checkpoint) => checkpoint,
+        None => LiquidityReplay::new(pool_address),
+    };
+    let head_block = provider.get_block_number().await?;
+    if to_block > head_block {
+        return Err(format!("to_block {} is past the chain head {}", to_block, head_block).into());
+    }
+    let from_block = match replay.block_number {
+        Some(block_number) => U64::from(block_number + 1),
+        None => U64::from(deployment.deployment_block),
+    };
+    if from_block > to_block {
+        return Ok(replay);
+    }
+
+    let event_filter = PoolEventFilter { event_types: vec!["mint".to_string(), "burn".to_string()], ..Default::default() };
+    let logs = get_pool_logs(provider, Filter::new().address(pool_address), from_block, to_block, &event_filter, max_concurrent_batches).await?;
+    for log in &logs {
+        let (event, _, block_number) = decode_uniswap_event(log)?;
+        replay.apply_event(block_number, &event);
+    }
+    replay.block_number = Some(to_block.as_u64());
+    Ok(replay)
+}
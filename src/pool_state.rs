@@ -0,0 +1,307 @@
+use crate::price_math::{price_strings, Price, MAX_TICK, MIN_TICK};
+use crate::{get_pool_abi, get_token_abis, get_token_info};
+use ethers::abi::{Detokenize, Tokenizable};
+use ethers::contract::{Contract, ContractCall, Multicall};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, BlockId, I256, U256, U64};
+use futures::future::join_all;
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Multicall3 has the same address on every supported chain
+const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+const MULTICALL_BATCH_SIZE: usize = 500; // Number of calls aggregated in each Multicall3 call
+
+// slot0(): (sqrtPriceX96, tick, observationIndex, observationCardinality, observationCardinalityNext, feeProtocol, unlocked).
+// feeProtocol is a uint8 on Uniswap V3 but a uint32 on PancakeSwap V3, so it is read into a u32.
+type Slot0 = (U256, i32, u16, u16, u16, u32, bool);
+
+#[derive(Debug, Serialize)]
+pub struct PoolState {
+    pub pool_address: Address,
+    pub block_number: u64,
+    pub token0: Address,
+    pub token1: Address,
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub observation_index: u16,
+    pub observation_cardinality: u16,
+    pub observation_cardinality_next: u16,
+    pub fee_protocol: u32,
+    pub unlocked: bool,
+    pub liquidity: U256,
+    pub fee_growth_global0_x128: U256,
+    pub fee_growth_global1_x128: U256,
+    pub protocol_fees_token0: U256,
+    pub protocol_fees_token1: U256,
+}
+
+// Read the pool state with every call pinned to `block_number`, the latest block when None
+pub async fn get_pool_state(
+    provider: Arc<Provider<Http>>,
+    pool_address: Address,
+    block_number: Option<U64>,
+) -> Result<PoolState, Box<dyn std::error::Error + Send + Sync>> {
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => provider.get_block_number().await?,
+    };
+    let block = BlockId::from(block_number);
+    let pool_contract = Contract::new(pool_address, get_pool_abi(), provider.clone());
+
+    let token0: Address = pool_contract.method::<(), Address>("token0", ())?.block(block).call().await?;
+    let token1: Address = pool_contract.method::<(), Address>("token1", ())?.block(block).call().await?;
+    let fee: u32 = pool_contract.method::<(), u32>("fee", ())?.block(block).call().await?;
+    let tick_spacing: i32 = pool_contract.method::<(), i32>("tickSpacing", ())?.block(block).call().await?;
+    let (sqrt_price_x96, tick, observation_index, observation_cardinality, observation_cardinality_next, fee_protocol, unlocked): Slot0 =
+        pool_contract.method::<(), Slot0>("slot0", ())?.block(block).call().await?;
+    let liquidity: U256 = pool_contract.method::<(), U256>("liquidity", ())?.block(block).call().await?;
+    let fee_growth_global0_x128: U256 = pool_contract.method::<(), U256>("feeGrowthGlobal0X128", ())?.block(block).call().await?;
+    let fee_growth_global1_x128: U256 = pool_contract.method::<(), U256>("feeGrowthGlobal1X128", ())?.block(block).call().await?;
+    let (protocol_fees_token0, protocol_fees_token1): (U256, U256) = pool_contract.method::<(), (U256, U256)>("protocolFees", ())?.block(block).call().await?;
+
+    Ok(PoolState {
+        pool_address,
+        block_number: block_number.as_u64(),
+        token0,
+        token1,
+        fee,
+        tick_spacing,
+        sqrt_price_x96,
+        tick,
+        observation_index,
+        observation_cardinality,
+        observation_cardinality_next,
+        fee_protocol,
+        unlocked,
+        liquidity,
+        fee_growth_global0_x128,
+        fee_growth_global1_x128,
+        protocol_fees_token0,
+        protocol_fees_token1,
+    })
+}
+
+// Run `calls` at `block_number` through Multicall3, MULTICALL_BATCH_SIZE calls at a time.
+// Blocks before Multicall3 was deployed fall back to one concurrent eth_call per call.
+pub async fn batch_call<D: Detokenize + Tokenizable>(
+    provider: Arc<Provider<Http>>,
+    calls: Vec<ContractCall<Provider<Http>, D>>,
+    block_number: U64,
+) -> Result<Vec<D>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut multicall = Multicall::new(provider.clone(), Some(Address::from_str(MULTICALL3_ADDRESS).unwrap())).await?
+        .block(block_number);
+    let mut results = Vec::with_capacity(calls.len());
+    for batch in calls.chunks(MULTICALL_BATCH_SIZE) {
+        multicall.clear_calls();
+        multicall.add_calls(false, batch.iter().cloned());
+        match multicall.call_array::<D>().await {
+            Ok(batch_results) => results.extend(batch_results),
+            Err(_) => {
+                let batch_results = join_all(batch.iter().map(|call| {
+                    let call = call.clone().block(block_number);
+                    async move { call.call().await }
+                })).await;
+                for result in batch_results {
+                    results.push(result?);
+                }
+            },
+        }
+    }
+    Ok(results)
+}
+
+// Range of tickBitmap word positions that can hold initialized ticks for `tick_spacing`
+pub fn tick_bitmap_word_range(tick_spacing: i32) -> (i16, i16) {
+    let min_word = (MIN_TICK.div_euclid(tick_spacing) >> 8) as i16;
+    let max_word = (MAX_TICK.div_euclid(tick_spacing) >> 8) as i16;
+    (min_word, max_word)
+}
+
+// Ticks whose bit is set in the tickBitmap word at `word_position`, in ascending order
+pub fn initialized_ticks_in_word(word_position: i16, word: U256, tick_spacing: i32) -> Vec<i32> {
+    (0..256)
+        .filter(|bit| word.bit(*bit))
+        .map(|bit| ((word_position as i32) * 256 + bit as i32) * tick_spacing)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TickLiquidity {
+    pub tick: i32,
+    pub liquidity_net: I256,
+    pub liquidity_gross: U256,
+    pub liquidity: U256,  // active liquidity while the price is between this tick and the next one
+}
+
+// Active liquidity above each tick: the running sum of liquidityNet from the lowest initialized tick
+pub fn accumulate_liquidity(ticks: &[(i32, I256, U256)]) -> Vec<TickLiquidity> {
+    let mut liquidity = I256::zero();
+    ticks.iter().map(|(tick, liquidity_net, liquidity_gross)| {
+        liquidity += *liquidity_net;
+        TickLiquidity {
+            tick: *tick,
+            liquidity_net: *liquidity_net,
+            liquidity_gross: *liquidity_gross,
+            liquidity: liquidity.into_raw(),
+        }
+    }).collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TickMap {
+    pub pool_address: Address,
+    pub block_number: u64,
+    pub tick_spacing: i32,
+    pub tick: i32,
+    pub sqrt_price_x96: U256,
+    pub liquidity: U256,
+    pub ticks: Vec<TickLiquidity>,
+}
+
+// ticks(tick): (liquidityGross, liquidityNet, feeGrowthOutside0X128, feeGrowthOutside1X128,
+// tickCumulativeOutside, secondsPerLiquidityOutsideX128, secondsOutside, initialized)
+type TickInfo = (U256, I256, U256, U256, I256, U256, u32, bool);
+
+// Active liquidity and current tick of the pool at `block_number`, None before the pool was created and initialized
+pub async fn get_active_liquidity(
+    provider: Arc<Provider<Http>>,
+    pool_address: Address,
+    block_number: U64,
+) -> Result<Option<(U256, i32)>, Box<dyn std::error::Error + Send + Sync>> {
+    let block = BlockId::from(block_number);
+    if provider.get_code(pool_address, Some(block)).await?.is_empty() {
+        return Ok(None);
+    }
+    let pool_contract = Contract::new(pool_address, get_pool_abi(), provider.clone());
+    let (sqrt_price_x96, tick, _, _, _, _, _): Slot0 = pool_contract.method::<(), Slot0>("slot0", ())?.block(block).call().await?;
+    if sqrt_price_x96.is_zero() {
+        return Ok(None);
+    }
+    let liquidity: U256 = pool_contract.method::<(), U256>("liquidity", ())?.block(block).call().await?;
+    Ok(Some((liquidity, tick)))
+}
+
+// Every initialized tick of the pool at `block_number`, found by scanning the tickBitmap words
+// over the pool's tick spacing and reading each set bit's tick
+pub async fn get_tick_map(
+    provider: Arc<Provider<Http>>,
+    pool_address: Address,
+    block_number: Option<U64>,
+) -> Result<TickMap, Box<dyn std::error::Error + Send + Sync>> {
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => provider.get_block_number().await?,
+    };
+    let block = BlockId::from(block_number);
+    let pool_contract = Contract::new(pool_address, get_pool_abi(), provider.clone());
+
+    let tick_spacing: i32 = pool_contract.method::<(), i32>("tickSpacing", ())?.block(block).call().await?;
+    let (sqrt_price_x96, tick, _, _, _, _, _): Slot0 = pool_contract.method::<(), Slot0>("slot0", ())?.block(block).call().await?;
+    let liquidity: U256 = pool_contract.method::<(), U256>("liquidity", ())?.block(block).call().await?;
+
+    let (min_word, max_word) = tick_bitmap_word_range(tick_spacing);
+    let word_positions: Vec<i16> = (min_word..=max_word).collect();
+    let word_calls = word_positions.iter()
+        .map(|word_position| pool_contract.method::<i16, U256>("tickBitmap", *word_position))
+        .collect::<Result<Vec<_>, _>>()?;
+    let words = batch_call(provider.clone(), word_calls, block_number).await?;
+    let initialized_ticks: Vec<i32> = word_positions.iter().zip(words)
+        .filter(|(_, word)| !word.is_zero())
+        .flat_map(|(word_position, word)| initialized_ticks_in_word(*word_position, word, tick_spacing))
+        .collect();
+
+    let tick_calls = initialized_ticks.iter()
+        .map(|tick| pool_contract.method::<i32, TickInfo>("ticks", *tick))
+        .collect::<Result<Vec<_>, _>>()?;
+    let tick_infos = batch_call(provider.clone(), tick_calls, block_number).await?;
+    let ticks: Vec<(i32, I256, U256)> = initialized_ticks.iter().zip(tick_infos)
+        .map(|(tick, (liquidity_gross, liquidity_net, _, _, _, _, _, _))| (*tick, liquidity_net, liquidity_gross))
+        .collect();
+
+    Ok(TickMap {
+        pool_address,
+        block_number: block_number.as_u64(),
+        tick_spacing,
+        tick,
+        sqrt_price_x96,
+        liquidity,
+        ticks: accumulate_liquidity(&ticks),
+    })
+}
+
+// Arithmetic mean tick over the window, rounded towards negative infinity like the periphery OracleLibrary
+pub fn arithmetic_mean_tick(tick_cumulative_delta: i64, window_seconds: u32) -> i32 {
+    let window_seconds = window_seconds as i64;
+    let mut mean_tick = tick_cumulative_delta / window_seconds;
+    if tick_cumulative_delta < 0 && tick_cumulative_delta % window_seconds != 0 {
+        mean_tick -= 1;
+    }
+    mean_tick as i32
+}
+
+// Difference of two secondsPerLiquidityCumulativeX128 observations. The uint160 accumulator wraps around, which the
+// pool's own OracleLibrary handles by subtracting modulo 2^160.
+pub fn seconds_per_liquidity_delta(start: U256, end: U256) -> U256 {
+    end.overflowing_sub(start).0 & ((U256::one() << 160) - 1)
+}
+
+// Harmonic mean liquidity over the window from the secondsPerLiquidityCumulativeX128 delta, like the periphery OracleLibrary.
+// None when no liquidity was in range for the whole window.
+pub fn harmonic_mean_liquidity(seconds_per_liquidity_delta: U256, window_seconds: u32) -> Option<U256> {
+    if seconds_per_liquidity_delta.is_zero() {
+        return None;
+    }
+    let seconds_ago_x160 = U256::from(window_seconds) * ((U256::one() << 160) - 1);
+    Some(seconds_ago_x160 / (seconds_per_liquidity_delta << 32))
+}
+
+// Time-weighted average price over the `window_seconds` before `block_number` from the pool's observe()
+pub async fn get_twap(
+    provider: Arc<Provider<Http>>,
+    pool_address: Address,
+    window_seconds: u32,
+    block_number: Option<U64>,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => provider.get_block_number().await?,
+    };
+    let block = BlockId::from(block_number);
+    let pool_contract = Contract::new(pool_address, get_pool_abi(), provider.clone());
+
+    let token0: Address = pool_contract.method::<(), Address>("token0", ())?.block(block).call().await?;
+    let token1: Address = pool_contract.method::<(), Address>("token1", ())?.block(block).call().await?;
+    let (tick_cumulatives, seconds_per_liquidity_cumulatives): (Vec<I256>, Vec<U256>) = pool_contract
+        .method::<Vec<u32>, (Vec<I256>, Vec<U256>)>("observe", vec![window_seconds, 0])?
+        .block(block)
+        .call()
+        .await?;
+    let tick_cumulative_delta = (tick_cumulatives[1] - tick_cumulatives[0]).as_i64();
+    let seconds_per_liquidity_delta = seconds_per_liquidity_delta(seconds_per_liquidity_cumulatives[0], seconds_per_liquidity_cumulatives[1]);
+
+    let token_abis = get_token_abis();
+    let (_, _, token0_decimals) = get_token_info(provider.clone(), token0, token_abis.clone()).await?;
+    let (_, _, token1_decimals) = get_token_info(provider.clone(), token1, token_abis).await?;
+    let token0_decimals = token0_decimals.as_u64().unwrap() as u32;
+    let token1_decimals = token1_decimals.as_u64().unwrap() as u32;
+
+    let mean_tick = arithmetic_mean_tick(tick_cumulative_delta, window_seconds);
+    let mean_liquidity = harmonic_mean_liquidity(seconds_per_liquidity_delta, window_seconds);
+    let (price, inverse_price) = price_strings(&Price::from_tick(mean_tick)?.adjust_decimals(token0_decimals, token1_decimals));
+    Ok(serde_json::json!({
+        "pool_address": pool_address,
+        "block_number": block_number.as_u64(),
+        "window_seconds": window_seconds,
+        "arithmetic_mean_tick": mean_tick,
+        "harmonic_mean_liquidity": mean_liquidity,
+        "price": price,
+        "inverse_price": inverse_price,
+        "token0": token0,
+        "token1": token1,
+    }))
+}
@@ -0,0 +1,378 @@
+use crate::deployments::Deployment;
+use crate::{compute_pool_address, get_logs_in_batches, serialize_logs_with, BurnEvent, MintEvent, BURN_EVENT_SIGNATURE, MINT_EVENT_SIGNATURE};
+use ethers::abi::{Abi, RawLog, Token};
+use ethers::contract::{Contract, EthLogDecode};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, BlockId, Filter, Log, H256, U256, U64};
+use futures::lock::Mutex;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+
+// NonfungiblePositionManager events tracked per position NFT
+const POSITION_MANAGER_EVENTS: [&str; 4] = ["IncreaseLiquidity", "DecreaseLiquidity", "Collect", "Transfer"];
+
+// positions(tokenId): (nonce, operator, token0, token1, fee, tickLower, tickUpper, liquidity,
+// feeGrowthInside0LastX128, feeGrowthInside1LastX128, tokensOwed0, tokensOwed1)
+type PositionInfo = (U256, Address, Address, Address, u32, i32, i32, U256, U256, U256, U256, U256);
+
+// The NonfungiblePositionManager ABI, which the token info lookup also uses for ERC721 metadata
+pub fn get_position_manager_abi() -> Abi {
+    serde_json::from_str(include_str!("contracts/erc721_abi.json")).unwrap()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidityChange {
+    pub token_id: U256,
+    pub liquidity: U256,
+    pub amount0: U256,
+    pub amount1: U256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionCollect {
+    pub token_id: U256,
+    pub recipient: Address,
+    pub amount0: U256,
+    pub amount1: U256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionTransfer {
+    pub from: Address,
+    pub to: Address,
+    pub token_id: U256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum PositionManagerEvent {
+    IncreaseLiquidity(LiquidityChange),
+    DecreaseLiquidity(LiquidityChange),
+    Collect(PositionCollect),
+    Transfer(PositionTransfer),
+}
+
+impl PositionManagerEvent {
+    pub fn token_id(&self) -> U256 {
+        match self {
+            PositionManagerEvent::IncreaseLiquidity(event) | PositionManagerEvent::DecreaseLiquidity(event) => event.token_id,
+            PositionManagerEvent::Collect(event) => event.token_id,
+            PositionManagerEvent::Transfer(event) => event.token_id,
+        }
+    }
+}
+
+fn get_event_signature(abi: &Abi, name: &str) -> H256 {
+    abi.event(name).unwrap().signature()
+}
+
+pub fn decode_position_manager_event(abi: &Abi, log: &Log) -> Result<PositionManagerEvent, Box<dyn std::error::Error + Send + Sync>> {
+    let topic0 = *log.topics.first().ok_or("Missing event signature")?;
+    let event = POSITION_MANAGER_EVENTS.iter()
+        .filter_map(|name| abi.event(name).ok())
+        .find(|event| event.signature() == topic0)
+        .ok_or_else(|| format!("Unknown position manager event signature: {:?}", topic0))?;
+    let parsed = event.parse_log(RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    })?;
+    let param = |name: &str| -> Result<Token, Box<dyn std::error::Error + Send + Sync>> {
+        parsed.params.iter()
+            .find(|param| param.name == name)
+            .map(|param| param.value.clone())
+            .ok_or_else(|| format!("Missing {} parameter {}", event.name, name).into())
+    };
+    let uint = |name: &str| -> Result<U256, Box<dyn std::error::Error + Send + Sync>> {
+        param(name)?.into_uint().ok_or_else(|| format!("{} parameter {} is not a uint", event.name, name).into())
+    };
+    let address = |name: &str| -> Result<Address, Box<dyn std::error::Error + Send + Sync>> {
+        param(name)?.into_address().ok_or_else(|| format!("{} parameter {} is not an address", event.name, name).into())
+    };
+
+    let decoded = match event.name.as_str() {
+        "IncreaseLiquidity" | "DecreaseLiquidity" => {
+            let change = LiquidityChange {
+                token_id: uint("tokenId")?,
+                liquidity: uint("liquidity")?,
+                amount0: uint("amount0")?,
+                amount1: uint("amount1")?,
+            };
+            if event.name == "IncreaseLiquidity" {
+                PositionManagerEvent::IncreaseLiquidity(change)
+            } else {
+                PositionManagerEvent::DecreaseLiquidity(change)
+            }
+        },
+        "Collect" => PositionManagerEvent::Collect(PositionCollect {
+            token_id: uint("tokenId")?,
+            recipient: address("recipient")?,
+            amount0: uint("amount0")?,
+            amount1: uint("amount1")?,
+        }),
+        _ => PositionManagerEvent::Transfer(PositionTransfer {
+            from: address("from")?,
+            to: address("to")?,
+            token_id: uint("tokenId")?,
+        }),
+    };
+    Ok(decoded)
+}
+
+fn position_manager_event_to_json(abi: &Abi, log: &Log) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let event = match decode_position_manager_event(abi, log)? {
+        PositionManagerEvent::IncreaseLiquidity(event) => serde_json::json!({ "type": "increase_liquidity", "data": event }),
+        PositionManagerEvent::DecreaseLiquidity(event) => serde_json::json!({ "type": "decrease_liquidity", "data": event }),
+        PositionManagerEvent::Collect(event) => serde_json::json!({ "type": "collect", "data": event }),
+        PositionManagerEvent::Transfer(event) => serde_json::json!({ "type": "transfer", "data": event }),
+    };
+    Ok(event)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Position {
+    pub token_id: U256,
+    pub owner: Option<Address>,  // None once the NFT is burned
+    pub pool_address: Option<Address>,
+    pub tick_lower: Option<i32>,
+    pub tick_upper: Option<i32>,
+    pub liquidity_added: U256,
+    pub liquidity_removed: U256,
+    pub burned: bool,
+}
+
+// Pool Mint or Burn in the same transaction as an IncreaseLiquidity or DecreaseLiquidity
+struct PoolLiquidityEvent {
+    pool_address: Address,
+    log_index: U256,
+    tick_lower: i32,
+    tick_upper: i32,
+    amount: U256,
+    amount0: U256,
+    amount1: U256,
+}
+
+fn decode_pool_liquidity_event(log: &Log) -> Result<(bool, PoolLiquidityEvent), Box<dyn std::error::Error + Send + Sync>> {
+    let raw_log = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    let log_index = log.log_index.unwrap_or_default();
+    if log.topics[0] == H256::from_str(MINT_EVENT_SIGNATURE).unwrap() {
+        let mint = <MintEvent as EthLogDecode>::decode_log(&raw_log)?;
+        Ok((true, PoolLiquidityEvent { pool_address: log.address, log_index, tick_lower: mint.tick_lower, tick_upper: mint.tick_upper, amount: mint.amount, amount0: mint.amount0, amount1: mint.amount1 }))
+    } else {
+        let burn = <BurnEvent as EthLogDecode>::decode_log(&raw_log)?;
+        Ok((false, PoolLiquidityEvent { pool_address: log.address, log_index, tick_lower: burn.tick_lower, tick_upper: burn.tick_upper, amount: burn.amount, amount0: burn.amount0, amount1: burn.amount1 }))
+    }
+}
+
+// Join the manager events with the pool Mint and Burn events it caused. The pool event is emitted
+// right before the manager event in the same transaction with the same liquidity and amounts, so the
+// closest preceding unmatched one is taken when a multicall touches several positions.
+pub fn build_positions(
+    manager_events: &[(Log, PositionManagerEvent)],
+    pool_logs: &[Log],
+) -> Result<BTreeMap<U256, Position>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut pool_events: HashMap<H256, Vec<(bool, PoolLiquidityEvent)>> = HashMap::new();
+    for log in pool_logs {
+        let transaction_hash = log.transaction_hash.ok_or("Missing transaction hash")?;
+        pool_events.entry(transaction_hash).or_default().push(decode_pool_liquidity_event(log)?);
+    }
+    let mut matched: HashSet<(H256, U256)> = HashSet::new();
+
+    let mut positions: BTreeMap<U256, Position> = BTreeMap::new();
+    for (log, event) in manager_events {
+        let token_id = event.token_id();
+        let position = positions.entry(token_id).or_insert_with(|| Position { token_id, ..Default::default() });
+        let (is_mint, change) = match event {
+            PositionManagerEvent::IncreaseLiquidity(change) => (true, change),
+            PositionManagerEvent::DecreaseLiquidity(change) => (false, change),
+            PositionManagerEvent::Collect(_) => continue,
+            PositionManagerEvent::Transfer(transfer) => {
+                if transfer.to.is_zero() {
+                    position.owner = None;
+                    position.burned = true;
+                } else {
+                    position.owner = Some(transfer.to);
+                }
+                continue;
+            },
+        };
+        if is_mint {
+            position.liquidity_added += change.liquidity;
+        } else {
+            position.liquidity_removed += change.liquidity;
+        }
+
+        let transaction_hash = log.transaction_hash.ok_or("Missing transaction hash")?;
+        let log_index = log.log_index.unwrap_or_default();
+        let pool_event = pool_events.get(&transaction_hash).and_then(|events| {
+            events.iter()
+                .filter(|(mint, pool_event)| *mint == is_mint
+                    && pool_event.log_index < log_index
+                    && pool_event.amount == change.liquidity
+                    && pool_event.amount0 == change.amount0
+                    && pool_event.amount1 == change.amount1
+                    && !matched.contains(&(transaction_hash, pool_event.log_index)))
+                .max_by_key(|(_, pool_event)| pool_event.log_index)
+        });
+        if let Some((_, pool_event)) = pool_event {
+            matched.insert((transaction_hash, pool_event.log_index));
+            position.pool_address = Some(pool_event.pool_address);
+            position.tick_lower = Some(pool_event.tick_lower);
+            position.tick_upper = Some(pool_event.tick_upper);
+        }
+    }
+    Ok(positions)
+}
+
+// Fill in the pool, tick range and owner of positions whose mint or last transfer happened before the queried range,
+// looking up to `max_concurrent_batches` positions at a time
+async fn complete_positions(
+    provider: Arc<Provider<Http>>,
+    deployment: &Deployment,
+    abi: &Abi,
+    positions: &mut BTreeMap<U256, Position>,
+    block_number: U64,
+    max_concurrent_batches: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let manager = Contract::new(deployment.position_manager, abi.clone(), provider.clone());
+    let block = BlockId::from(block_number);
+    let lookups: Vec<(U256, bool, bool)> = positions
+        .values()
+        .filter(|position| !position.burned && (position.pool_address.is_none() || position.owner.is_none()))
+        .map(|position| (position.token_id, position.pool_address.is_none(), position.owner.is_none()))
+        .collect();
+    let results: Vec<(U256, Option<PositionInfo>, Option<Address>)> = stream::iter(lookups)
+        .map(|(token_id, lookup_info, lookup_owner)| {
+            let manager = manager.clone();
+            async move {
+                // positions() reverts for burned NFTs, leaving the position unresolved
+                let info = if lookup_info {
+                    let result: Result<PositionInfo, _> = manager.method("positions", token_id)?.block(block).call().await;
+                    result.ok()
+                } else {
+                    None
+                };
+                let owner = if lookup_owner {
+                    let result: Result<Address, _> = manager.method("ownerOf", token_id)?.block(block).call().await;
+                    result.ok()
+                } else {
+                    None
+                };
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>((token_id, info, owner))
+            }
+        })
+        .buffered(max_concurrent_batches)
+        .try_collect()
+        .await?;
+
+    for (token_id, info, owner) in results {
+        let position = positions.get_mut(&token_id).unwrap();
+        if let Some((_, _, token0, token1, fee, tick_lower, tick_upper, _, _, _, _, _)) = info {
+            position.pool_address = Some(compute_pool_address(deployment.pool_deployer, token0, token1, fee, deployment.init_code_hash));
+            position.tick_lower = Some(tick_lower);
+            position.tick_upper = Some(tick_upper);
+        }
+        if owner.is_some() {
+            position.owner = owner;
+        }
+    }
+    Ok(())
+}
+
+// Pool Mint and Burn logs of the position manager in the transactions of the IncreaseLiquidity and DecreaseLiquidity
+// among `logs`. The pools are unknown until these are matched, so instead of scanning every pool over the whole range
+// only the blocks of those transactions are queried, up to `max_concurrent_batches` at a time.
+async fn get_pool_logs_of_transactions(
+    provider: Arc<Provider<Http>>,
+    deployment: &Deployment,
+    abi: &Abi,
+    logs: &[Log],
+    max_concurrent_batches: usize,
+) -> Result<Vec<Log>, Box<dyn std::error::Error + Send + Sync>> {
+    let liquidity_signatures: Vec<H256> = POSITION_MANAGER_EVENTS[..2].iter().map(|name| get_event_signature(abi, name)).collect();
+    let liquidity_logs: Vec<&Log> = logs.iter().filter(|log| log.topics.first().is_some_and(|topic0| liquidity_signatures.contains(topic0))).collect();
+    let transaction_hashes: HashSet<H256> = liquidity_logs.iter().filter_map(|log| log.transaction_hash).collect();
+    let block_numbers: BTreeSet<U64> = liquidity_logs.iter().filter_map(|log| log.block_number).collect();
+
+    let filter = Filter::new()
+        .topic0(vec![H256::from_str(MINT_EVENT_SIGNATURE).unwrap(), H256::from_str(BURN_EVENT_SIGNATURE).unwrap()])
+        .topic1(H256::from(deployment.position_manager));
+    let blocks: Vec<Vec<Log>> = stream::iter(block_numbers)
+        .map(|block_number| {
+            let provider = provider.clone();
+            let block_filter = filter.clone().from_block(block_number).to_block(block_number);
+            async move { provider.get_logs(&block_filter).await }
+        })
+        .buffered(max_concurrent_batches)
+        .try_collect()
+        .await?;
+    Ok(blocks.into_iter()
+        .flatten()
+        .filter(|log| log.transaction_hash.is_some_and(|transaction_hash| transaction_hashes.contains(&transaction_hash)))
+        .collect())
+}
+
+// Fetch the position manager events of `token_ids` (every position when empty) and map each position NFT
+// to its pool, tick range and owner as of `to_block`
+pub async fn get_position_events(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    deployment: &Deployment,
+    from_block: U64,
+    to_block: U64,
+    token_ids: Vec<U256>,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let abi = get_position_manager_abi();
+    let base_filter = Filter::new().address(deployment.position_manager);
+    let token_id_topics: Vec<H256> = token_ids.iter().map(|token_id| {
+        let mut topic = [0u8; 32];
+        token_id.to_big_endian(&mut topic);
+        H256::from(topic)
+    }).collect();
+    // tokenId is topic1 of the liquidity and collect events but topic3 of Transfer
+    let filters = if token_ids.is_empty() {
+        vec![base_filter.topic0(POSITION_MANAGER_EVENTS.iter().map(|name| get_event_signature(&abi, name)).collect::<Vec<H256>>())]
+    } else {
+        vec![
+            base_filter.clone()
+                .topic0(POSITION_MANAGER_EVENTS[..3].iter().map(|name| get_event_signature(&abi, name)).collect::<Vec<H256>>())
+                .topic1(token_id_topics.clone()),
+            base_filter.topic0(get_event_signature(&abi, "Transfer")).topic3(token_id_topics),
+        ]
+    };
+    let mut logs = Vec::new();
+    for filter in filters {
+        logs.extend(get_logs_in_batches(provider.clone(), filter, from_block, to_block, max_concurrent_batches).await?);
+    }
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    // The pool records the position manager as the owner of every position it manages. Every position needs every
+    // Mint and Burn of the manager in the range, a few positions only those of their own transactions.
+    let pool_logs = if token_ids.is_empty() {
+        let pool_filter = Filter::new()
+            .topic0(vec![H256::from_str(MINT_EVENT_SIGNATURE).unwrap(), H256::from_str(BURN_EVENT_SIGNATURE).unwrap()])
+            .topic1(H256::from(deployment.position_manager));
+        get_logs_in_batches(provider.clone(), pool_filter, from_block, to_block, max_concurrent_batches).await?
+    } else {
+        get_pool_logs_of_transactions(provider.clone(), deployment, &abi, &logs, max_concurrent_batches).await?
+    };
+
+    let manager_events = logs.iter()
+        .map(|log| decode_position_manager_event(&abi, log).map(|event| (log.clone(), event)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut positions = build_positions(&manager_events, &pool_logs)?;
+    for token_id in &token_ids {
+        positions.entry(*token_id).or_insert_with(|| Position { token_id: *token_id, ..Default::default() });
+    }
+    complete_positions(provider.clone(), deployment, &abi, &mut positions, to_block, max_concurrent_batches).await?;
+
+    let mut events = serialize_logs_with(logs, provider, block_cache, |log| position_manager_event_to_json(&abi, log)).await?;
+    events.as_object_mut().unwrap().insert("positions".to_string(), serde_json::json!(positions.values().collect::<Vec<_>>()));
+    Ok(events)
+}
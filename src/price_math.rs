@@ -0,0 +1,178 @@
+use ethers::types::U256;
+use num_bigint::BigUint;
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
+// getSqrtRatioAtTick(MIN_TICK) and getSqrtRatioAtTick(MAX_TICK)
+const MIN_SQRT_RATIO: &str = "4295128739";
+const MAX_SQRT_RATIO: &str = "1461446703485210103287273052203988822378723970342";
+// Prices that have no finite decimal expansion are truncated to this many significant digits
+const PRICE_SIGNIFICANT_DIGITS: usize = 40;
+
+// sqrt(1.0001)^-(2^i) in Q128.128 for the bits of the absolute tick above the lowest one, as in TickMath.sol
+const TICK_RATIO_FACTORS: [(u32, &str); 19] = [
+    (0x2, "fff97272373d413259a46990580e213a"),
+    (0x4, "fff2e50f5f656932ef12357cf3c7fdcc"),
+    (0x8, "ffe5caca7e10e4e61c3624eaa0941cd0"),
+    (0x10, "ffcb9843d60f6159c9db58835c926644"),
+    (0x20, "ff973b41fa98c081472e6896dfb254c0"),
+    (0x40, "ff2ea16466c96a3843ec78b326b52861"),
+    (0x80, "fe5dee046a99a2a811c461f1969c3053"),
+    (0x100, "fcbe86c7900a88aedcffc83b479aa3a4"),
+    (0x200, "f987a7253ac413176f2b074cf7815e54"),
+    (0x400, "f3392b0822b70005940c7a398e4b70f3"),
+    (0x800, "e7159475a2c29b7443b29c7fa6e889d9"),
+    (0x1000, "d097f3bdfd2022b8845ad8f792aa5825"),
+    (0x2000, "a9f746462d870fdf8a65dc1f90e061e5"),
+    (0x4000, "70d869a156d2a1b890bb3df62baf32f7"),
+    (0x8000, "31be135f97d08fd981231505542fcfa6"),
+    (0x10000, "9aa508b5b7a84e1c677de54f3e99bc9"),
+    (0x20000, "5d6af8dedb81196699c329225ee604"),
+    (0x40000, "2216e584f5fa1ea926041bedfe98"),
+    (0x80000, "48a170391f7dc42444e8fa2"),
+];
+
+pub fn u256_to_biguint(value: U256) -> BigUint {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    BigUint::from_bytes_be(&bytes)
+}
+
+// sqrt(1.0001^tick) as a Q64.96, bit for bit what TickMath.getSqrtRatioAtTick returns on chain
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256, String> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(format!("Tick {} is outside [{}, {}]", tick, MIN_TICK, MAX_TICK));
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from_str_radix("fffcb933bd6fad37aa2d162d1a594001", 16).unwrap()
+    } else {
+        U256::one() << 128
+    };
+    for (bit, factor) in TICK_RATIO_FACTORS {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * U256::from_str_radix(factor, 16).unwrap()) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+    // Q128.128 to Q64.96, rounding up so that getTickAtSqrtRatio is consistent with it
+    let rounding = if (ratio % (U256::one() << 32)).is_zero() { U256::zero() } else { U256::one() };
+    Ok((ratio >> 32) + rounding)
+}
+
+// Greatest tick whose sqrt ratio is at most `sqrt_price_x96`, like TickMath.getTickAtSqrtRatio
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32, String> {
+    let min_sqrt_ratio = U256::from_dec_str(MIN_SQRT_RATIO).unwrap();
+    let max_sqrt_ratio = U256::from_dec_str(MAX_SQRT_RATIO).unwrap();
+    if sqrt_price_x96 < min_sqrt_ratio || sqrt_price_x96 >= max_sqrt_ratio {
+        return Err(format!("sqrtPriceX96 {} is outside [{}, {})", sqrt_price_x96, MIN_SQRT_RATIO, MAX_SQRT_RATIO));
+    }
+    // The sqrt ratio increases with the tick, so the tick is found by binary search
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let middle = low + (high - low + 1) / 2;
+        if get_sqrt_ratio_at_tick(middle)? <= sqrt_price_x96 {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    Ok(low)
+}
+
+// An exact non-negative rational price
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    pub numerator: BigUint,
+    pub denominator: BigUint,
+}
+
+impl Price {
+    pub fn new(numerator: BigUint, denominator: BigUint) -> Self {
+        Price { numerator, denominator }
+    }
+
+    // Raw price of token0 in token1 (token1 per token0) from a Q64.96 square root price
+    pub fn from_sqrt_price_x96(sqrt_price_x96: U256) -> Self {
+        let sqrt_price = u256_to_biguint(sqrt_price_x96);
+        Price::new(&sqrt_price * &sqrt_price, BigUint::from(1u8) << 192)
+    }
+
+    // Raw price of token0 in token1 at `tick`, from the on-chain sqrt ratio of the tick
+    pub fn from_tick(tick: i32) -> Result<Self, String> {
+        Ok(Price::from_sqrt_price_x96(get_sqrt_ratio_at_tick(tick)?))
+    }
+
+    // Convert a raw token0/token1 price between base units into a price between whole tokens
+    pub fn adjust_decimals(self, token0_decimals: u32, token1_decimals: u32) -> Self {
+        Price::new(
+            self.numerator * BigUint::from(10u8).pow(token0_decimals),
+            self.denominator * BigUint::from(10u8).pow(token1_decimals),
+        )
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator == BigUint::from(0u8)
+    }
+
+    // The price in the other orientation, None for a zero price
+    pub fn invert(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        Some(Price::new(self.denominator.clone(), self.numerator.clone()))
+    }
+
+    // Whether the decimal expansion is finite, i.e. the reduced denominator has no prime factors but 2 and 5
+    fn terminates(&self) -> bool {
+        let zero = BigUint::from(0u8);
+        let five = BigUint::from(5u8);
+        let mut denominator = self.denominator.clone();
+        if let Some(twos) = denominator.trailing_zeros() {
+            denominator >>= twos;
+        }
+        while &denominator % &five == zero {
+            denominator /= &five;
+        }
+        &self.numerator % &denominator == zero
+    }
+
+    // Exact decimal string when the expansion is finite, otherwise truncated to PRICE_SIGNIFICANT_DIGITS significant digits
+    pub fn to_decimal_string(&self) -> String {
+        let zero = BigUint::from(0u8);
+        let ten = BigUint::from(10u8);
+        let integer = &self.numerator / &self.denominator;
+        let mut remainder = &self.numerator % &self.denominator;
+        let mut result = integer.to_string();
+        if remainder == zero {
+            return result;
+        }
+        let terminates = self.terminates();
+        let mut significant_digits = if integer == zero { 0 } else { result.len() };
+        let mut fraction = String::new();
+        while remainder != zero && (terminates || significant_digits < PRICE_SIGNIFICANT_DIGITS) {
+            remainder *= &ten;
+            let digit = &remainder / &self.denominator;
+            remainder %= &self.denominator;
+            if significant_digits > 0 || digit != zero {
+                significant_digits += 1;
+            }
+            fraction.push_str(&digit.to_string());
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if !fraction.is_empty() {
+            result.push('.');
+            result.push_str(fraction);
+        }
+        result
+    }
+}
+
+// Price of token0 in token1 and of token1 in token0 between whole tokens, as decimal strings.
+// The inverse of a zero price is "0".
+pub fn price_strings(price: &Price) -> (String, String) {
+    let inverse = price.invert().map(|inverse| inverse.to_decimal_string()).unwrap_or_else(|| "0".to_string());
+    (price.to_decimal_string(), inverse)
+}
@@ -0,0 +1,241 @@
+use crate::pool_state::get_active_liquidity;
+use crate::price_math::{u256_to_biguint, Price};
+use crate::{
+    decode_uniswap_event, get_block_number_range, get_block_timestamp, get_pool_abi, get_pool_info, get_pool_logs,
+    get_token_abis, get_token_info, PoolEventFilter, UniswapEvent,
+};
+use chrono::Utc;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, Filter, U256, U64};
+use futures::lock::Mutex;
+use num_bigint::{BigInt, BigUint};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// A position of the pool: owner, tickLower and tickUpper
+type PositionKey = (Address, i32, i32);
+
+// Liquidity changes and collected fees of one position inside a window
+#[derive(Default)]
+struct PositionActivity {
+    liquidity_added: U256,
+    liquidity_removed: U256,
+    fees0: U256,
+    fees1: U256,
+}
+
+// Swaps, volumes, liquidity and fees of one window
+#[derive(Default)]
+struct SignalWindow {
+    swap_count: u64,
+    sqrt_price_squares: BigUint,
+    volume0: U256,
+    volume1: U256,
+    liquidity_start: Option<U256>,
+    liquidity_end: Option<U256>,
+    liquidity_added: U256,
+    liquidity_removed: U256,
+    fees0: U256,
+    fees1: U256,
+    positions: BTreeMap<PositionKey, PositionActivity>,
+}
+
+fn token_amount(amount: U256, decimals: u32) -> String {
+    Price::new(u256_to_biguint(amount), BigUint::from(10u8).pow(decimals)).to_decimal_string()
+}
+
+impl SignalWindow {
+    fn add_swap(&mut self, sqrt_price_x96: U256, volume0: U256, volume1: U256) {
+        self.swap_count += 1;
+        self.sqrt_price_squares += Price::from_sqrt_price_x96(sqrt_price_x96).numerator;
+        self.volume0 += volume0;
+        self.volume1 += volume1;
+    }
+
+    fn position(&mut self, owner: Address, tick_lower: i32, tick_upper: i32) -> &mut PositionActivity {
+        self.positions.entry((owner, tick_lower, tick_upper)).or_default()
+    }
+
+    fn to_json(&self, start_timestamp: u64, end_timestamp: u64, token0_decimals: u32, token1_decimals: u32) -> Value {
+        // Mean price of token0 in token1 after each swap, exact over the common 2^192 denominator of the squared sqrt prices.
+        // None without swaps.
+        let price = (self.swap_count > 0).then(|| {
+            Price::new(self.sqrt_price_squares.clone(), BigUint::from(self.swap_count) << 192)
+                .adjust_decimals(token0_decimals, token1_decimals)
+                .to_decimal_string()
+        });
+        let liquidity_delta = BigInt::from(u256_to_biguint(self.liquidity_added)) - BigInt::from(u256_to_biguint(self.liquidity_removed));
+        let positions: Vec<Value> = self.positions.iter().map(|((owner, tick_lower, tick_upper), activity)| {
+            serde_json::json!({
+                "owner": owner,
+                "tick_lower": tick_lower,
+                "tick_upper": tick_upper,
+                "liquidity_added": activity.liquidity_added.to_string(),
+                "liquidity_removed": activity.liquidity_removed.to_string(),
+                "fees0": token_amount(activity.fees0, token0_decimals),
+                "fees1": token_amount(activity.fees1, token1_decimals),
+            })
+        }).collect();
+        serde_json::json!({
+            "start_timestamp": start_timestamp,
+            "end_timestamp": end_timestamp,
+            "price": price,
+            "swap_count": self.swap_count,
+            "volume0": token_amount(self.volume0, token0_decimals),
+            "volume1": token_amount(self.volume1, token1_decimals),
+            "liquidity_start": self.liquidity_start.map(|liquidity| liquidity.to_string()),
+            "liquidity_end": self.liquidity_end.map(|liquidity| liquidity.to_string()),
+            "liquidity_added": self.liquidity_added.to_string(),
+            "liquidity_removed": self.liquidity_removed.to_string(),
+            "liquidity_delta": liquidity_delta.to_string(),
+            "fees0": token_amount(self.fees0, token0_decimals),
+            "fees1": token_amount(self.fees1, token1_decimals),
+            "positions": positions,
+        })
+    }
+}
+
+// Pool state carried across windows while the events are replayed in order
+struct SignalState {
+    // Active liquidity and current tick, None until known
+    active: Option<(U256, i32)>,
+    // Token amounts burned positions are owed, which their next collects withdraw before any fees
+    owed: HashMap<PositionKey, (U256, U256)>,
+}
+
+impl SignalState {
+    fn apply(&mut self, window: &mut SignalWindow, event: &UniswapEvent) {
+        match event {
+            UniswapEvent::Swap(swap) => {
+                window.add_swap(swap.sqrt_price_x96, swap.amount0.unsigned_abs(), swap.amount1.unsigned_abs());
+                self.active = Some((swap.liquidity, swap.tick));
+            },
+            UniswapEvent::PancakeSwap(swap) => {
+                window.add_swap(swap.sqrt_price_x96, swap.amount0.unsigned_abs(), swap.amount1.unsigned_abs());
+                self.active = Some((swap.liquidity, swap.tick));
+            },
+            UniswapEvent::Initialize(initialize) => self.active = Some((U256::zero(), initialize.tick)),
+            UniswapEvent::Mint(mint) => {
+                window.liquidity_added += mint.amount;
+                window.position(mint.owner, mint.tick_lower, mint.tick_upper).liquidity_added += mint.amount;
+                // Liquidity is only active while the current tick is inside the position's range
+                if let Some((liquidity, tick)) = self.active.as_mut() {
+                    if mint.tick_lower <= *tick && *tick < mint.tick_upper {
+                        *liquidity += mint.amount;
+                    }
+                }
+            },
+            UniswapEvent::Burn(burn) => {
+                window.liquidity_removed += burn.amount;
+                window.position(burn.owner, burn.tick_lower, burn.tick_upper).liquidity_removed += burn.amount;
+                if let Some((liquidity, tick)) = self.active.as_mut() {
+                    if burn.tick_lower <= *tick && *tick < burn.tick_upper {
+                        *liquidity = liquidity.saturating_sub(burn.amount);
+                    }
+                }
+                let owed = self.owed.entry((burn.owner, burn.tick_lower, burn.tick_upper)).or_default();
+                owed.0 += burn.amount0;
+                owed.1 += burn.amount1;
+            },
+            UniswapEvent::Collect(collect) => {
+                let key = (collect.owner, collect.tick_lower, collect.tick_upper);
+                let owed = self.owed.entry(key).or_default();
+                let principal0 = collect.amount0.min(owed.0);
+                let principal1 = collect.amount1.min(owed.1);
+                owed.0 -= principal0;
+                owed.1 -= principal1;
+                let (fees0, fees1) = (collect.amount0 - principal0, collect.amount1 - principal1);
+                window.fees0 += fees0;
+                window.fees1 += fees1;
+                let position = window.position(collect.owner, collect.tick_lower, collect.tick_upper);
+                position.fees0 += fees0;
+                position.fees1 += fees1;
+            },
+            _ => {},
+        }
+    }
+}
+
+// Aggregate pool events, given in order with their block timestamps, into consecutive windows of `interval` seconds
+// starting at `start_timestamp`. The last window is cut at `end_timestamp`. `active` is the active liquidity and
+// current tick before the first event, from which the absolute liquidity is followed through the events.
+// Collected amounts count as fees once they exceed what the position's burns in the range are owed, so
+// principal burned before the range and collected inside it is counted as fees.
+pub fn build_signals(
+    events: &[(u64, UniswapEvent)],
+    start_timestamp: u64,
+    end_timestamp: u64,
+    interval: u64,
+    active: Option<(U256, i32)>,
+    token0_decimals: u32,
+    token1_decimals: u32,
+) -> Vec<Value> {
+    let mut state = SignalState { active, owed: HashMap::new() };
+    let mut events = events.iter()
+        .filter(|(timestamp, _)| (start_timestamp..=end_timestamp).contains(timestamp))
+        .peekable();
+    let mut signals = Vec::new();
+    let mut window_start = start_timestamp;
+    while window_start <= end_timestamp {
+        let window_end = (window_start + interval - 1).min(end_timestamp);
+        let mut window = SignalWindow {
+            liquidity_start: state.active.map(|(liquidity, _)| liquidity),
+            ..Default::default()
+        };
+        while let Some((_, event)) = events.next_if(|(timestamp, _)| *timestamp <= window_end) {
+            state.apply(&mut window, event);
+        }
+        window.liquidity_end = state.active.map(|(liquidity, _)| liquidity);
+        signals.push(window.to_json(window_start, window_end, token0_decimals, token1_decimals));
+        window_start += interval;
+    }
+    signals
+}
+
+// Signals of the pool over consecutive windows of `interval` seconds from `start_timestamp` to `end_timestamp`,
+// a single window when `end_timestamp` is not given
+pub async fn get_signals_by_pool_address(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    pool_address: Address,
+    start_timestamp: u64,
+    interval: u64,
+    end_timestamp: Option<u64>,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>> {
+    // A window reaching into the future is cut at the current time
+    let end_timestamp = end_timestamp
+        .unwrap_or(start_timestamp + interval - 1)
+        .min(Utc::now().timestamp() as u64);
+    let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
+
+    let token_abis = get_token_abis();
+    let (token0, token1, _, _) = get_pool_info(provider.clone(), pool_address, get_pool_abi()).await?;
+    let (_, _, token0_decimals) = get_token_info(provider.clone(), token0, token_abis.clone()).await?;
+    let (_, _, token1_decimals) = get_token_info(provider.clone(), token1, token_abis).await?;
+    // The state right before the first block of the range
+    let active = get_active_liquidity(provider.clone(), pool_address, start_block_number.saturating_sub(U64::one())).await?;
+
+    let event_filter = PoolEventFilter {
+        event_types: ["initialize", "swap", "mint", "burn", "collect"].iter().map(|event_type| event_type.to_string()).collect(),
+        ..Default::default()
+    };
+    let logs = get_pool_logs(provider.clone(), Filter::new().address(pool_address), start_block_number, end_block_number, &event_filter, max_concurrent_batches).await?;
+    let mut events = Vec::new();
+    for log in &logs {
+        let (event, _, block_number) = decode_uniswap_event(log)?;
+        let timestamp = get_block_timestamp(provider.clone(), block_cache.clone(), block_number).await?;
+        events.push((timestamp, event));
+    }
+
+    Ok(build_signals(
+        &events,
+        start_timestamp,
+        end_timestamp,
+        interval,
+        active,
+        token0_decimals.as_u64().unwrap() as u32,
+        token1_decimals.as_u64().unwrap() as u32,
+    ))
+}
@@ -0,0 +1,278 @@
+use crate::deployments::V2Deployment;
+use crate::{get_block_number_range, get_cached_token_info, get_logs_in_batches, get_token_abis, serialize_logs_with, validate_event_types, TokenInfoCache};
+use ethers::abi::RawLog;
+use ethers::contract::{EthEvent, EthLogDecode};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Filter, Log, H256, U256, U64};
+use futures::{future::join_all, lock::Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::max;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use pyo3::prelude::*;
+
+pub const PAIR_CREATED_SIGNATURE: &str = "0d3648bd0f6ba80134a33ba9275ac585d9d315f0ad8355cddefde31afa28d0e9";
+pub const V2_SWAP_EVENT_SIGNATURE: &str = "d78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
+pub const V2_MINT_EVENT_SIGNATURE: &str = "4c209b5fc8ad50758f13e2e1088ba56a560dff690a1c6fef26394f4c03821c4f";
+pub const V2_BURN_EVENT_SIGNATURE: &str = "dccd412f0b1252819cb1fd330b93224ca42612892bb3f4f789976e6d81936496";
+pub const V2_SYNC_EVENT_SIGNATURE: &str = "1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1";
+
+const V2_PAIR_EVENT_TYPES: [&str; 4] = ["swap", "mint", "burn", "sync"];
+
+#[derive(Debug, EthEvent, Serialize)]
+#[ethevent(name = "PairCreated", abi = "PairCreated(address indexed token0, address indexed token1, address pair, uint256 allPairsLength)")]
+struct PairCreatedEvent {
+    token0: Address,
+    token1: Address,
+    pair: Address,
+    all_pairs_length: U256,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Swap", abi = "Swap(address indexed sender, uint256 amount0In, uint256 amount1In, uint256 amount0Out, uint256 amount1Out, address indexed to)")]
+pub struct V2SwapEvent {
+    pub sender: Address,
+    pub amount0_in: U256,
+    pub amount1_in: U256,
+    pub amount0_out: U256,
+    pub amount1_out: U256,
+    pub to: Address,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Mint", abi = "Mint(address indexed sender, uint256 amount0, uint256 amount1)")]
+pub struct V2MintEvent {
+    pub sender: Address,
+    pub amount0: U256,
+    pub amount1: U256,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Burn", abi = "Burn(address indexed sender, uint256 amount0, uint256 amount1, address indexed to)")]
+pub struct V2BurnEvent {
+    pub sender: Address,
+    pub amount0: U256,
+    pub amount1: U256,
+    pub to: Address,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Sync", abi = "Sync(uint112 reserve0, uint112 reserve1)")]
+pub struct V2SyncEvent {
+    pub reserve0: U256,  // uint112 fits in U256
+    pub reserve1: U256,
+}
+
+#[derive(Debug, Serialize)]
+pub enum UniswapV2Event {
+    Swap(V2SwapEvent),
+    Mint(V2MintEvent),
+    Burn(V2BurnEvent),
+    Sync(V2SyncEvent),
+}
+
+pub fn decode_v2_event(log: &Log) -> Result<UniswapV2Event, Box<dyn std::error::Error + Send + Sync>> {
+    let raw_log = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    let topic0 = *log.topics.first().ok_or("Missing event signature")?;
+    if topic0 == H256::from_str(V2_SWAP_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV2Event::Swap(<V2SwapEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else if topic0 == H256::from_str(V2_MINT_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV2Event::Mint(<V2MintEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else if topic0 == H256::from_str(V2_BURN_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV2Event::Burn(<V2BurnEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else if topic0 == H256::from_str(V2_SYNC_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV2Event::Sync(<V2SyncEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else {
+        Err(format!("Unknown V2 pair event signature: {:?}", topic0).into())
+    }
+}
+
+fn v2_event_to_json(log: &Log) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let event = match decode_v2_event(log)? {
+        UniswapV2Event::Swap(event) => serde_json::json!({ "type": "swap", "data": event }),
+        UniswapV2Event::Mint(event) => serde_json::json!({ "type": "mint", "data": event }),
+        UniswapV2Event::Burn(event) => serde_json::json!({ "type": "burn", "data": event }),
+        UniswapV2Event::Sync(event) => serde_json::json!({ "type": "sync", "data": event }),
+    };
+    Ok(event)
+}
+
+fn get_v2_pair_event_signature(event_type: &str) -> Option<H256> {
+    let signature = match event_type {
+        "swap" => V2_SWAP_EVENT_SIGNATURE,
+        "mint" => V2_MINT_EVENT_SIGNATURE,
+        "burn" => V2_BURN_EVENT_SIGNATURE,
+        "sync" => V2_SYNC_EVENT_SIGNATURE,
+        _ => return None,
+    };
+    Some(H256::from_str(signature).unwrap())
+}
+
+pub fn parse_v2_event_types(event_types: Option<Vec<String>>) -> PyResult<Vec<String>> {
+    validate_event_types(event_types, &V2_PAIR_EVENT_TYPES, &V2_PAIR_EVENT_TYPES)
+}
+
+// Derive the pair address the V2 factory creates with CREATE2, without any RPC call
+pub fn compute_v2_pair_address(factory_address: Address, token_a: Address, token_b: Address, init_code_hash: H256) -> Address {
+    let (token0, token1) = if token_a < token_b { (token_a, token_b) } else { (token_b, token_a) };
+    let salt = ethers::utils::keccak256(ethers::abi::encode_packed(&[
+        ethers::abi::Token::Address(token0),
+        ethers::abi::Token::Address(token1),
+    ]).unwrap());
+    ethers::utils::get_create2_address_from_hash(factory_address, salt, init_code_hash)
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairResolution {
+    token0: Address,
+    token1: Address,
+    pair_address: Option<Address>,
+    status: &'static str,  // "found" or "missing"
+}
+
+// Resolve the pair of every (token0, token1) and check that it is deployed at `block_number`
+async fn resolve_v2_pair_addresses(
+    provider: Arc<Provider<Http>>,
+    deployment: &V2Deployment,
+    token_pairs: Vec<(String, String)>,
+    block_number: U64,
+) -> Result<Vec<PairResolution>, Box<dyn std::error::Error + Send + Sync>> {
+    let factory_address = deployment.factory_address;
+    let init_code_hash = deployment.init_code_hash;
+
+    let futures = token_pairs.into_iter().map(|(token0, token1)| {
+        let provider = provider.clone();
+        async move {
+            let token0_address = Address::from_str(&token0)?;
+            let token1_address = Address::from_str(&token1)?;
+            let mut resolution = PairResolution {
+                token0: token0_address,
+                token1: token1_address,
+                pair_address: None,
+                status: "missing",
+            };
+            if token0_address == token1_address {
+                return Ok(resolution);
+            }
+            let pair_address = compute_v2_pair_address(factory_address, token0_address, token1_address, init_code_hash);
+            resolution.pair_address = Some(pair_address);
+            let code = provider.get_code(pair_address, Some(block_number.into())).await?;
+            if !code.is_empty() {
+                resolution.status = "found";
+            }
+            Ok(resolution) as Result<PairResolution, Box<dyn std::error::Error + Send + Sync>>
+        }
+    });
+
+    join_all(futures).await.into_iter().collect()
+}
+
+pub async fn get_v2_pair_events_by_pair_addresses(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    pair_addresses: Vec<Address>,
+    from_block: U64,
+    to_block: U64,
+    event_types: Vec<String>,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let signatures: Vec<H256> = event_types.iter().filter_map(|event_type| get_v2_pair_event_signature(event_type)).collect();
+    let filter = Filter::new().address(pair_addresses).topic0(signatures);
+    let mut logs = get_logs_in_batches(provider.clone(), filter, from_block, to_block, max_concurrent_batches).await?;
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    serialize_logs_with(logs, provider, block_cache, v2_event_to_json).await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn get_v2_pair_events_by_token_pairs(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    deployment: &V2Deployment,
+    token_pairs: Vec<(String, String)>,
+    from_block: U64,
+    to_block: U64,
+    event_types: Vec<String>,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let pairs = resolve_v2_pair_addresses(provider.clone(), deployment, token_pairs, to_block).await?;
+    let pair_addresses: Vec<Address> = pairs.iter()
+        .filter(|pair| pair.status == "found")
+        .filter_map(|pair| pair.pair_address)
+        .collect();
+
+    // An empty address list would make the node return logs from every contract, so skip the query entirely
+    let mut events = if pair_addresses.is_empty() {
+        serialize_logs_with(Vec::new(), provider.clone(), block_cache.clone(), v2_event_to_json).await?
+    } else {
+        get_v2_pair_events_by_pair_addresses(provider.clone(), block_cache.clone(), pair_addresses, from_block, to_block, event_types, max_concurrent_batches).await?
+    };
+    events.as_object_mut().unwrap().insert("pairs".to_string(), serde_json::json!(pairs));
+    Ok(events)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_v2_pair_data(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    deployment: &V2Deployment,
+    token_pairs: Vec<(String, String)>,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    event_types: Vec<String>,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let (from_block, to_block) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
+    get_v2_pair_events_by_token_pairs(provider, block_cache, deployment, token_pairs, from_block, to_block, event_types, max_concurrent_batches).await
+}
+
+pub async fn get_v2_pair_created_events_between_two_timestamps(
+    provider: Arc<Provider<Http>>,
+    token_info_cache: TokenInfoCache,
+    deployment: &V2Deployment,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>> {
+    let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
+    // No pairs exist before the factory was deployed
+    let start_block_number = max(start_block_number, U64::from(deployment.deployment_block));
+    let filter = Filter::new()
+        .address(deployment.factory_address)
+        .topic0(H256::from_str(PAIR_CREATED_SIGNATURE).unwrap());
+    let logs = get_logs_in_batches(provider.clone(), filter, start_block_number, end_block_number, max_concurrent_batches).await?;
+    let abis = get_token_abis();
+
+    let mut pair_created_events = Vec::new();
+    for log in logs {
+        let raw_log = RawLog {
+            topics: log.topics.clone(),
+            data: log.data.to_vec(),
+        };
+        let pair_created_event = <PairCreatedEvent as EthLogDecode>::decode_log(&raw_log)?;
+        let token0_info = get_cached_token_info(provider.clone(), token_info_cache.clone(), pair_created_event.token0, abis.clone()).await;
+        let token1_info = get_cached_token_info(provider.clone(), token_info_cache.clone(), pair_created_event.token1, abis.clone()).await;
+        pair_created_events.push(serde_json::json!({
+            "token0": {
+                "address": pair_created_event.token0,
+                "name": token0_info.0,
+                "symbol": token0_info.1,
+                "decimals": token0_info.2,
+            },
+            "token1": {
+                "address": pair_created_event.token1,
+                "name": token1_info.0,
+                "symbol": token1_info.1,
+                "decimals": token1_info.2,
+            },
+            "pair_address": pair_created_event.pair,
+            "block_number": log.block_number.unwrap().as_u64(),
+            "protocol": "uniswap_v2",
+        }));
+    }
+    Ok(pair_created_events)
+}
@@ -0,0 +1,285 @@
+use crate::deployments::V4Deployment;
+use crate::{get_block_number_range, get_logs_in_batches, serialize_logs_with, validate_event_types};
+use ethers::abi::RawLog;
+use ethers::contract::{EthEvent, EthLogDecode};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, Filter, Log, H256, I256, U256, U64};
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use pyo3::prelude::*;
+
+pub const V4_INITIALIZE_EVENT_SIGNATURE: &str = "dd466e674ea557f56295e2d0218a125ea4b4f0f6f3307b95f85e6110838d6438";
+pub const V4_MODIFY_LIQUIDITY_EVENT_SIGNATURE: &str = "f208f4912782fd25c7f114ca3723a2d5dd6f3bcc3ac8db5af63baa85f711d5ec";
+pub const V4_SWAP_EVENT_SIGNATURE: &str = "40e9cecb9f5f1f1c5b9c97dec2917b7ee92e57ba5563708daca94dd84ad7112f";
+pub const V4_DONATE_EVENT_SIGNATURE: &str = "29ef05caaff9404b7cb6d1c0e9bbae9eaa7ab2541feba1a9c4248594c08156cb";
+
+const V4_POOL_EVENT_TYPES: [&str; 4] = ["initialize", "modify_liquidity", "swap", "donate"];
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Initialize", abi = "Initialize(bytes32 indexed id, address indexed currency0, address indexed currency1, uint24 fee, int24 tickSpacing, address hooks, uint160 sqrtPriceX96, int24 tick)")]
+pub struct V4InitializeEvent {
+    pub id: H256,
+    pub currency0: Address,  // the zero address is native ETH
+    pub currency1: Address,
+    pub fee: u32,  // uint24 fits in u32
+    pub tick_spacing: i32,
+    pub hooks: Address,
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "ModifyLiquidity", abi = "ModifyLiquidity(bytes32 indexed id, address indexed sender, int24 tickLower, int24 tickUpper, int256 liquidityDelta, bytes32 salt)")]
+pub struct V4ModifyLiquidityEvent {
+    pub id: H256,
+    pub sender: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity_delta: I256,
+    pub salt: H256,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Swap", abi = "Swap(bytes32 indexed id, address indexed sender, int128 amount0, int128 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick, uint24 fee)")]
+pub struct V4SwapEvent {
+    pub id: H256,
+    pub sender: Address,
+    pub amount0: I256,  // int128 fits in I256
+    pub amount1: I256,
+    pub sqrt_price_x96: U256,
+    pub liquidity: U256,
+    pub tick: i32,
+    pub fee: u32,
+}
+
+#[derive(Debug, EthEvent, Serialize, Deserialize)]
+#[ethevent(name = "Donate", abi = "Donate(bytes32 indexed id, address indexed sender, uint256 amount0, uint256 amount1)")]
+pub struct V4DonateEvent {
+    pub id: H256,
+    pub sender: Address,
+    pub amount0: U256,
+    pub amount1: U256,
+}
+
+#[derive(Debug, Serialize)]
+pub enum UniswapV4Event {
+    Initialize(V4InitializeEvent),
+    ModifyLiquidity(V4ModifyLiquidityEvent),
+    Swap(V4SwapEvent),
+    Donate(V4DonateEvent),
+}
+
+pub fn decode_v4_event(log: &Log) -> Result<UniswapV4Event, Box<dyn std::error::Error + Send + Sync>> {
+    let raw_log = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    let topic0 = *log.topics.first().ok_or("Missing event signature")?;
+    if topic0 == H256::from_str(V4_INITIALIZE_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV4Event::Initialize(<V4InitializeEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else if topic0 == H256::from_str(V4_MODIFY_LIQUIDITY_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV4Event::ModifyLiquidity(<V4ModifyLiquidityEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else if topic0 == H256::from_str(V4_SWAP_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV4Event::Swap(<V4SwapEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else if topic0 == H256::from_str(V4_DONATE_EVENT_SIGNATURE).unwrap() {
+        Ok(UniswapV4Event::Donate(<V4DonateEvent as EthLogDecode>::decode_log(&raw_log)?))
+    } else {
+        Err(format!("Unknown V4 PoolManager event signature: {:?}", topic0).into())
+    }
+}
+
+fn v4_event_to_json(log: &Log) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let event = match decode_v4_event(log)? {
+        UniswapV4Event::Initialize(event) => serde_json::json!({ "type": "initialize", "data": event }),
+        UniswapV4Event::ModifyLiquidity(event) => serde_json::json!({ "type": "modify_liquidity", "data": event }),
+        UniswapV4Event::Swap(event) => serde_json::json!({ "type": "swap", "data": event }),
+        UniswapV4Event::Donate(event) => serde_json::json!({ "type": "donate", "data": event }),
+    };
+    Ok(event)
+}
+
+fn get_v4_pool_event_signature(event_type: &str) -> Option<H256> {
+    let signature = match event_type {
+        "initialize" => V4_INITIALIZE_EVENT_SIGNATURE,
+        "modify_liquidity" => V4_MODIFY_LIQUIDITY_EVENT_SIGNATURE,
+        "swap" => V4_SWAP_EVENT_SIGNATURE,
+        "donate" => V4_DONATE_EVENT_SIGNATURE,
+        _ => return None,
+    };
+    Some(H256::from_str(signature).unwrap())
+}
+
+pub fn parse_v4_event_types(event_types: Option<Vec<String>>) -> PyResult<Vec<String>> {
+    validate_event_types(event_types, &V4_POOL_EVENT_TYPES, &V4_POOL_EVENT_TYPES)
+}
+
+// PoolId is keccak256(abi.encode(PoolKey)); currencies are sorted like the PoolManager requires
+pub fn compute_v4_pool_id(currency_a: Address, currency_b: Address, fee: u32, tick_spacing: i32, hooks: Address) -> H256 {
+    let (currency0, currency1) = if currency_a < currency_b { (currency_a, currency_b) } else { (currency_b, currency_a) };
+    H256::from(ethers::utils::keccak256(ethers::abi::encode(&[
+        ethers::abi::Token::Address(currency0),
+        ethers::abi::Token::Address(currency1),
+        ethers::abi::Token::Uint(U256::from(fee)),
+        ethers::abi::Token::Int(I256::from(tick_spacing).into_raw()),
+        ethers::abi::Token::Address(hooks),
+    ])))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolKey {
+    currency0: Address,
+    currency1: Address,
+    fee: u32,
+    tick_spacing: i32,
+    hooks: Address,
+}
+
+#[derive(Debug, Serialize)]
+pub struct V4PoolResolution {
+    pool_id: H256,
+    pool_key: Option<PoolKey>,
+    initialize_block: Option<u64>,
+    status: &'static str,  // "found" or "missing"
+}
+
+// PoolKeys and Initialize blocks of the pools resolved so far. A pool is initialized once, so a found pool is never
+// scanned for again; missing pools are not cached as they may be initialized later.
+pub type V4PoolKeyCache = Arc<Mutex<HashMap<H256, (PoolKey, Option<u64>)>>>;
+
+// Resolve the PoolKey of every pool id from its Initialize event, which the PoolManager emits once per pool. Only
+// the pools missing from `pool_key_cache` are looked up, from the PoolManager deployment to `to_block`. The cache is
+// not locked during the lookup, so concurrent calls do not wait for each other's scans.
+async fn resolve_v4_pool_keys(
+    provider: Arc<Provider<Http>>,
+    pool_key_cache: V4PoolKeyCache,
+    deployment: &V4Deployment,
+    pool_ids: &[H256],
+    to_block: U64,
+    max_concurrent_batches: usize,
+) -> Result<Vec<V4PoolResolution>, Box<dyn std::error::Error + Send + Sync>> {
+    let unresolved: Vec<H256> = {
+        let cache = pool_key_cache.lock().await;
+        pool_ids.iter().filter(|pool_id| !cache.contains_key(*pool_id)).copied().collect()
+    };
+    let mut resolved = Vec::new();
+    if !unresolved.is_empty() {
+        let filter = Filter::new()
+            .address(deployment.pool_manager)
+            .topic0(H256::from_str(V4_INITIALIZE_EVENT_SIGNATURE).unwrap())
+            .topic1(unresolved);
+        let logs = get_logs_in_batches(provider, filter, U64::from(deployment.deployment_block), to_block, max_concurrent_batches).await?;
+        for log in logs {
+            if let UniswapV4Event::Initialize(event) = decode_v4_event(&log)? {
+                let pool_key = PoolKey {
+                    currency0: event.currency0,
+                    currency1: event.currency1,
+                    fee: event.fee,
+                    tick_spacing: event.tick_spacing,
+                    hooks: event.hooks,
+                };
+                resolved.push((event.id, (pool_key, log.block_number.map(|block_number| block_number.as_u64()))));
+            }
+        }
+    }
+
+    let mut cache = pool_key_cache.lock().await;
+    cache.extend(resolved);
+
+    Ok(pool_ids.iter().map(|pool_id| match cache.get(pool_id) {
+        // A pool cached by a call reaching further than `to_block` may not be initialized yet at `to_block`
+        Some((pool_key, initialize_block)) if initialize_block.is_none_or(|block_number| block_number <= to_block.as_u64()) => V4PoolResolution {
+            pool_id: *pool_id,
+            pool_key: Some(pool_key.clone()),
+            initialize_block: *initialize_block,
+            status: "found",
+        },
+        _ => V4PoolResolution {
+            pool_id: *pool_id,
+            pool_key: None,
+            initialize_block: None,
+            status: "missing",
+        },
+    }).collect())
+}
+
+// Fetch the PoolManager events of the given pools, matched on the PoolId in topic1
+#[allow(clippy::too_many_arguments)]
+pub async fn get_v4_pool_events_by_pool_ids(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    pool_key_cache: V4PoolKeyCache,
+    deployment: &V4Deployment,
+    pool_ids: Vec<H256>,
+    from_block: U64,
+    to_block: U64,
+    event_types: Vec<String>,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let signatures: Vec<H256> = event_types.iter().filter_map(|event_type| get_v4_pool_event_signature(event_type)).collect();
+    let filter = Filter::new()
+        .address(deployment.pool_manager)
+        .topic0(signatures)
+        .topic1(pool_ids.clone());
+    let mut logs = get_logs_in_batches(provider.clone(), filter, from_block, to_block, max_concurrent_batches).await?;
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    let pools = resolve_v4_pool_keys(provider.clone(), pool_key_cache, deployment, &pool_ids, to_block, max_concurrent_batches).await?;
+    let mut events = serialize_logs_with(logs, provider, block_cache, v4_event_to_json).await?;
+    events.as_object_mut().unwrap().insert("pools".to_string(), serde_json::json!(pools));
+    Ok(events)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_v4_pool_data(
+    provider: Arc<Provider<Http>>,
+    block_cache: Arc<Mutex<HashMap<u64, u64>>>,
+    pool_key_cache: V4PoolKeyCache,
+    deployment: &V4Deployment,
+    pool_ids: Vec<H256>,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    event_types: Vec<String>,
+    max_concurrent_batches: usize,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let (from_block, to_block) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
+    get_v4_pool_events_by_pool_ids(provider, block_cache, pool_key_cache, deployment, pool_ids, from_block, to_block, event_types, max_concurrent_batches).await
+}
+
+// V4 has no factory, so pool discovery goes through the PoolManager's Initialize events
+pub async fn get_v4_pool_initialized_events_between_two_timestamps(
+    provider: Arc<Provider<Http>>,
+    deployment: &V4Deployment,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    max_concurrent_batches: usize,
+) -> Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>> {
+    let (start_block_number, end_block_number) = get_block_number_range(provider.clone(), start_timestamp, end_timestamp).await?;
+    let start_block_number = std::cmp::max(start_block_number, U64::from(deployment.deployment_block));
+    let filter = Filter::new()
+        .address(deployment.pool_manager)
+        .topic0(H256::from_str(V4_INITIALIZE_EVENT_SIGNATURE).unwrap());
+    let logs = get_logs_in_batches(provider.clone(), filter, start_block_number, end_block_number, max_concurrent_batches).await?;
+
+    let mut pool_initialized_events = Vec::new();
+    for log in logs {
+        if let UniswapV4Event::Initialize(event) = decode_v4_event(&log)? {
+            pool_initialized_events.push(serde_json::json!({
+                "pool_id": event.id,
+                "currency0": event.currency0,
+                "currency1": event.currency1,
+                "fee": event.fee,
+                "tick_spacing": event.tick_spacing,
+                "hooks": event.hooks,
+                "sqrt_price_x96": event.sqrt_price_x96,
+                "tick": event.tick,
+                "block_number": log.block_number.unwrap().as_u64(),
+                "protocol": "uniswap_v4",
+            }));
+        }
+    }
+    Ok(pool_initialized_events)
+}
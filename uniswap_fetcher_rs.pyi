@@ -1,17 +1,50 @@
-from typing import List, Tuple, Dict, Any, Union
+from typing import List, Tuple, Dict, Any, Union, Optional
 
 class UniswapFetcher:
-    def __init__(self, rpc_url: str) -> None:
+    def __init__(self, rpc_url: str, chain_id: Optional[int] = None, protocol: str = "uniswap_v3", max_concurrent_batches: int = 4) -> None:
         """
         Initialize the UniswapFetcher.
 
         Args:
             rpc_url (str): The RPC URL of the Ethereum node.
+            chain_id (Optional[int]): Chain to use the deployment of. Detected with eth_chainId on the first call that
+                needs it when omitted, so construction does not contact the node. Local development chains, such as
+                anvil or hardhat forks reporting 31337 or ganache reporting 1337, use the Ethereum mainnet deployment.
+                Raises ValueError for any other chain the protocol is not registered on, when constructing with a
+                given chain id, otherwise on the first call.
+            protocol (str): Protocol profile selecting the factory, pool init code hash and event ABI variants.
+                "uniswap_v3": Ethereum (1), Optimism (10), Polygon (137), Base (8453) and Arbitrum One (42161).
+                "sushiswap_v3": Ethereum (1).
+                "pancakeswap_v3": Ethereum (1) and BNB Chain (56).
+                Raises ValueError for unknown protocols.
+            max_concurrent_batches (int): Number of 10001 block windows whose logs are requested from the node at
+                once. Results are assembled in block order whatever the limit. Raises ValueError for 0.
         Examples:
         >>> uniswap_fetcher = UniswapFetcher("https://mainnet.infura.io/v3/YOUR_INFURA_PROJECT_ID")
             initialize the UniswapFetcher with the RPC URL "https://mainnet.infura.io/v3/YOUR_INFURA_PROJECT_ID".
         Recommended:
         Use local node for faster response time.
+        Threads:
+        Fetches release the GIL, so other Python threads keep running and several fetchers can run at once from a
+        thread pool. Ctrl-C cancels a fetch running on the main thread and raises KeyboardInterrupt.
+        Asyncio:
+        Every fetch method has an `_async` variant taking the same arguments, which must be called from a running
+        event loop and returns an awaitable. Invalid arguments raise right away. Cancelling the awaitable stops the
+        fetch and its in-flight requests.
+        """
+        ...
+
+    @property
+    def chain_id(self) -> int:
+        """
+        Chain ID of the deployment this fetcher queries.
+        """
+        ...
+
+    @property
+    def protocol(self) -> str:
+        """
+        Protocol profile of the deployment this fetcher queries, e.g. "uniswap_v3".
         """
         ...
 
@@ -19,7 +52,10 @@ class UniswapFetcher:
         self,
         token_pairs: List[Tuple[str, str, int]],
         from_block: int,
-        to_block: int
+        to_block: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None,
+        verify_pool_addresses: bool = False
     ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
         """
         Get pool events by token pairs.
@@ -28,6 +64,17 @@ class UniswapFetcher:
             token_pairs (List[Tuple[str, str, int]]): List of token pairs and fees.
             from_block (int): Starting block number.
             to_block (int): Ending block number.
+            event_types (Optional[List[str]]): Event types to fetch. Defaults to
+                ["swap", "mint", "burn", "collect", "flash"]. Also accepts "initialize",
+                "set_fee_protocol", "collect_protocol" and "increase_observation_cardinality_next".
+                Raises ValueError for unknown event types.
+            topic_filter (Optional[Dict[str, Union[str, int, List[str], List[int]]]]): Match indexed event
+                parameters on the node. Keys are "sender", "recipient", "owner", "tick_lower" and "tick_upper";
+                values are a single address / tick or a list of them. sender and recipient apply to swap, flash
+                and collect_protocol events, owner and the tick bounds apply to mint, burn and collect events.
+                Event types without the filtered parameters are skipped.
+            verify_pool_addresses (bool): Pool addresses are derived offline with CREATE2. When True, each one is
+                also checked against the factory's getPool at to_block and the factory's answer wins on a mismatch.
 
         Returns:
             Dict: JSON object containing the pool events.
@@ -37,7 +84,7 @@ class UniswapFetcher:
                         "event": {
                             "type": str,
                             "data": {
-                                SwapEvent, MintEvent, BurnEvent
+                                SwapEvent, MintEvent, BurnEvent, CollectEvent, FlashEvent
                             }
                         },
                         "block_number": int,
@@ -49,7 +96,19 @@ class UniswapFetcher:
                     ...
                 ]
                 overall_hash: str
+                protocol: str
+                pools: [
+                    {
+                        "token0": str,
+                        "token1": str,
+                        "fee": int,
+                        "pool_address": Optional[str],
+                        "status": str  # "found", "missing" or "invalid_fee_tier"
+                    },
+                    ...
+                ]
             }
+            Events are only fetched for pools with status "found".
         Examples:
         >>> uniswap_fetcher.get_pool_events_by_token_pairs([("0x6b175474e89094c44da98b954eedeac495", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", 3000)], 10000000, 10000001)
             fetch pool events for the token pair ("0x6b175474e89094c44da98b954", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2") with fee 3000 between the block numbers 10000000 and 10000001.
@@ -57,6 +116,20 @@ class UniswapFetcher:
         """
         ...
 
+    async def get_pool_events_by_token_pairs_async(
+        self,
+        token_pairs: List[Tuple[str, str, int]],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None,
+        verify_pool_addresses: bool = False
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of get_pool_events_by_token_pairs.
+        """
+        ...
+
     def get_block_number_range(
         self,
         start_timestamp: int,
@@ -77,11 +150,24 @@ class UniswapFetcher:
         """
         ...
 
+    async def get_block_number_range_async(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> List[int]:
+        """
+        Awaitable variant of get_block_number_range, resolving to a [start, end] list.
+        """
+        ...
+
     def fetch_pool_data(
         self,
         token_pairs: List[Tuple[str, str, int]],
         start_timestamp: int,
-        end_timestamp: int
+        end_timestamp: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None,
+        verify_pool_addresses: bool = False
     ) -> Dict:
         """
         Fetch pool data for the given token pairs within the specified time range.
@@ -90,6 +176,17 @@ class UniswapFetcher:
             token_pairs (List[Tuple[str, str, int]]): List of token pairs and fees.
             start_timestamp (int): Starting timstamp.
             end_timestamp (int): Ending timstamp.
+            event_types (Optional[List[str]]): Event types to fetch. Defaults to
+                ["swap", "mint", "burn", "collect", "flash"]. Also accepts "initialize",
+                "set_fee_protocol", "collect_protocol" and "increase_observation_cardinality_next".
+                Raises ValueError for unknown event types.
+            topic_filter (Optional[Dict[str, Union[str, int, List[str], List[int]]]]): Match indexed event
+                parameters on the node. Keys are "sender", "recipient", "owner", "tick_lower" and "tick_upper";
+                values are a single address / tick or a list of them. sender and recipient apply to swap, flash
+                and collect_protocol events, owner and the tick bounds apply to mint, burn and collect events.
+                Event types without the filtered parameters are skipped.
+            verify_pool_addresses (bool): Pool addresses are derived offline with CREATE2. When True, each one is
+                also checked against the factory's getPool at to_block and the factory's answer wins on a mismatch.
 
         Returns:
             Dict: JSON object containing the pool events.
@@ -99,7 +196,7 @@ class UniswapFetcher:
                         "event": {
                             "type": str,
                             "data": {
-                                SwapEvent, MintEvent, BurnEvent
+                                SwapEvent, MintEvent, BurnEvent, CollectEvent, FlashEvent
                             }
                         },
                         "block_number": int,
@@ -111,13 +208,39 @@ class UniswapFetcher:
                     ...
                 ]
                 overall_hash: str
+                protocol: str
+                pools: [
+                    {
+                        "token0": str,
+                        "token1": str,
+                        "fee": int,
+                        "pool_address": Optional[str],
+                        "status": str  # "found", "missing" or "invalid_fee_tier"
+                    },
+                    ...
+                ]
             }
+            Events are only fetched for pools with status "found".
         Examples:
         >>> uniswap_fetcher.fetch_pool_data([("0x6b175474e89094c44da98b954eedeac495271d0f", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", 3000)], 1620000000, 1620000001)
             fetch pool data for the token pair ("0x6b175474e89094c44da98b954eedeac495271d0f", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2") with fee 3000 between the timestamps 1620000000 and 1620000001.
         """
         ...
 
+    async def fetch_pool_data_async(
+        self,
+        token_pairs: List[Tuple[str, str, int]],
+        start_timestamp: int,
+        end_timestamp: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None,
+        verify_pool_addresses: bool = False
+    ) -> Dict:
+        """
+        Awaitable variant of fetch_pool_data.
+        """
+        ...
+
     def get_pool_created_events_between_two_timestamps(
         self,
         start_timestamp: int,
@@ -139,7 +262,8 @@ class UniswapFetcher:
                     "pool_address": str, 
                     "block_number": int,
                     "fee": int,
-                    "tick_spacing": int
+                    "tick_spacing": int,
+                    "protocol": str
                 },
                 ...
             ]
@@ -148,28 +272,88 @@ class UniswapFetcher:
             fetch pool created events between the timestamps 1620000000 and 1620000001.
         """
         ...
+
+    async def get_pool_created_events_between_two_timestamps_async(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> Dict:
+        """
+        Awaitable variant of get_pool_created_events_between_two_timestamps.
+        """
+        ...
         
     def get_signals_by_pool_address(
         self,
         pool_address: str,
         timestamp: int,
-        interval: int
-    ) -> Dict:
+        interval: int,
+        end_timestamp: Optional[int] = None
+    ) -> List[Dict[str, Union[int, str]]]:
         """
-        Get signals by pool address.
+        Get signals by pool address over consecutive time windows. Timestamps are resolved to blocks with the same
+        block search as get_block_number_range. The active liquidity is read from the pool right before the range
+        and followed through the Swap, Mint and Burn events. Collected amounts count as fees once they exceed what
+        the position's burns in the range are owed, so principal burned before the range is counted as fees.
 
         Args:
             pool_address (str): Pool address.
-            timestamp (int): Timestamp.
+            timestamp (int): Start timestamp of the first window.
             interval (int): signal interval in seconds.
+            end_timestamp (Optional[int]): Last timestamp covered, the last window is cut there. Defaults to a single
+                window of interval seconds. Windows reaching into the future are cut at the current time.
 
         Returns:
-            Dict: JSON object containing the signals.
-            [{"price": str, "volume": str, "liquidity": str}, ...]
+            List: Signals of each window, ordered by time.
+            [
+                {
+                    "start_timestamp": int,
+                    "end_timestamp": int,  # inclusive
+                    "price": Optional[str],  # mean price of token0 in token1 after each swap, adjusted for the token decimals, None without swaps
+                    "swap_count": int,
+                    "volume0": str,  # token0 swapped in either direction, in whole tokens
+                    "volume1": str,  # token1 swapped in either direction, in whole tokens
+                    "liquidity_start": Optional[str],  # active liquidity at the window start, None before the pool was initialized
+                    "liquidity_end": Optional[str],  # active liquidity at the window end
+                    "liquidity_added": str,  # liquidity minted
+                    "liquidity_removed": str,  # liquidity burned
+                    "liquidity_delta": str,  # liquidity minted minus liquidity burned
+                    "fees0": str,  # token0 fees collected, in whole tokens
+                    "fees1": str,  # token1 fees collected, in whole tokens
+                    "positions": [
+                        {
+                            "owner": str,
+                            "tick_lower": int,
+                            "tick_upper": int,
+                            "liquidity_added": str,
+                            "liquidity_removed": str,
+                            "fees0": str,
+                            "fees1": str
+                        },
+                        ...
+                    ]  # positions with mints, burns or collects in the window
+                },
+                ...
+            ]
         Examples:
         >>> uniswap_fetcher.
-            get_signals_by_pool_address("0x1f98407aaB862CdDeF78Ed252D6f557aA5b0f00d", 1620000000, 3600)
-            fetch signals for the pool address "0x1f98407aaB862CdDeF78Ed252D6f557aA5b0f00d" at timestamp 1620000000 with 1 hour interval.
+            get_signals_by_pool_address("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8", 1620000000, 3600)
+            fetch signals for the USDC/WETH 0.3% pool for the hour from timestamp 1620000000.
+        >>> uniswap_fetcher.
+            get_signals_by_pool_address("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8", 1620000000, 300, 1620003599)
+            fetch the 12 signals of 5 minutes each in that hour.
+        """
+        ...
+
+    async def get_signals_by_pool_address_async(
+        self,
+        pool_address: str,
+        timestamp: int,
+        interval: int,
+        end_timestamp: Optional[int] = None
+    ) -> List[Dict[str, Union[int, str]]]:
+        """
+        Awaitable variant of get_signals_by_pool_address.
         """
         ...
     
@@ -178,7 +362,9 @@ class UniswapFetcher:
         self,
         pool_addresses: List[str],
         from_block: int,
-        to_block: int
+        to_block: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None
     ) -> Dict:
         """
         Get pool events by pool addresses.
@@ -187,6 +373,15 @@ class UniswapFetcher:
             pool_addresses (List[str]): List of pool addresses.
             from_block (int): Starting block number.
             to_block (int): Ending block number.
+            event_types (Optional[List[str]]): Event types to fetch. Defaults to
+                ["swap", "mint", "burn", "collect", "flash"]. Also accepts "initialize",
+                "set_fee_protocol", "collect_protocol" and "increase_observation_cardinality_next".
+                Raises ValueError for unknown event types.
+            topic_filter (Optional[Dict[str, Union[str, int, List[str], List[int]]]]): Match indexed event
+                parameters on the node. Keys are "sender", "recipient", "owner", "tick_lower" and "tick_upper";
+                values are a single address / tick or a list of them. sender and recipient apply to swap, flash
+                and collect_protocol events, owner and the tick bounds apply to mint, burn and collect events.
+                Event types without the filtered parameters are skipped.
 
         Returns:
             Dict: JSON object containing the pool events.
@@ -196,7 +391,9 @@ class UniswapFetcher:
                         "event": {
                             "type": str,
                             "data": {
-                                SwapEvent, MintEvent, BurnEvent
+                                SwapEvent, MintEvent, BurnEvent, CollectEvent, FlashEvent,
+                                InitializeEvent, SetFeeProtocolEvent, CollectProtocolEvent,
+                                IncreaseObservationCardinalityNextEvent
                             }
                         },
                         "block_number": int,
@@ -208,7 +405,51 @@ class UniswapFetcher:
                     ...
                 ]
                 overall_hash: str
+                protocol: str
+            }
+        """
+        ...
+
+    async def get_pool_events_by_pool_addresses_async(
+        self,
+        pool_addresses: List[str],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None
+    ) -> Dict:
+        """
+        Awaitable variant of get_pool_events_by_pool_addresses.
+        """
+        ...
+
+    def iter_pool_events_by_pool_addresses(
+        self,
+        pool_addresses: List[str],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None
+    ) -> "PoolEventStream":
+        """
+        Iterate over the pool events of a block range window by window, without holding the whole range in memory.
+
+        Args:
+            Same as get_pool_events_by_pool_addresses. Raises ValueError for an empty or invalid pool address.
+
+        Returns:
+            PoolEventStream: Iterator fetching one window of 10001 blocks per step and yielding
+            {
+                data: [...]  # the events of the window, as in get_pool_events_by_pool_addresses
+                overall_data_hash: str  # hash of this window's data
+                protocol: str
+                from_block: int
+                to_block: int
             }
+            Every window is yielded, including windows without events.
+        Examples:
+        >>> for batch in uniswap_fetcher.iter_pool_events_by_pool_addresses(["0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"], 12370000, 13370000):
+        ...     store(batch["data"])
         """
         ...
     
@@ -253,16 +494,50 @@ class UniswapFetcher:
             [("0x6b175474e89094c44da98b954eedeac495271d0f", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", 3000, "0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8"), ...]
         """
         ...
+
+    async def get_all_token_pairs_async(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> List[Tuple[str, str, int, str]]:
+        """
+        Awaitable variant of get_all_token_pairs.
+        """
+        ...
+
+    async def get_all_tokens_async(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> List[str]:
+        """
+        Awaitable variant of get_all_tokens.
+        """
+        ...
     
     def get_recent_pool_events(
         self,
         pool_address: str,
         start_timestamp: int,
-        end_timestamp: int
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None
     ) -> Dict:
         """
         Get recent pool events by pool address.
-        
+
+        Args:
+            pool_address (str): Pool address.
+            start_timestamp (int): Starting timestamp. Events are fetched up to the latest block.
+            event_types (Optional[List[str]]): Event types to fetch. Defaults to
+                ["swap", "mint", "burn", "collect", "flash"]. Also accepts "initialize",
+                "set_fee_protocol", "collect_protocol" and "increase_observation_cardinality_next".
+                Raises ValueError for unknown event types.
+            topic_filter (Optional[Dict[str, Union[str, int, List[str], List[int]]]]): Match indexed event
+                parameters on the node. Keys are "sender", "recipient", "owner", "tick_lower" and "tick_upper";
+                values are a single address / tick or a list of them. sender and recipient apply to swap, flash
+                and collect_protocol events, owner and the tick bounds apply to mint, burn and collect events.
+                Event types without the filtered parameters are skipped.
+
         Returns:
             Dict: JSON object containing the pool events.
             {
@@ -283,10 +558,41 @@ class UniswapFetcher:
                     ...
                 ]
                 overall_hash: str
+                protocol: str
             }
         """
         ...
+
+    async def get_recent_pool_events_async(
+        self,
+        pool_address: str,
+        start_timestamp: int,
+        event_types: Optional[List[str]] = None,
+        topic_filter: Optional[Dict[str, Any]] = None
+    ) -> Dict:
+        """
+        Awaitable variant of get_recent_pool_events.
+        """
+        ...
     
+    def get_timestamp_by_block_number(self, block_number: int) -> int:
+        """
+        Get the timestamp of a block.
+
+        Args:
+            block_number (int): Block number.
+
+        Returns:
+            int: Unix timestamp of the block.
+        """
+        ...
+
+    async def get_timestamp_by_block_number_async(self, block_number: int) -> int:
+        """
+        Awaitable variant of get_timestamp_by_block_number.
+        """
+        ...
+
     def get_pool_price_ratios(
         self,
         pool_address: str,
@@ -302,10 +608,851 @@ class UniswapFetcher:
             [
                 {
                     "timestamp": int,
-                    "price_ratio": str
+                    "price_ratio": str,  # exact price of token0 in token1, adjusted for the token decimals
+                    "inverse_price_ratio": str  # price of token1 in token0, "0" until the first known price
+                },
+                ...
+            ]
+        """
+        ...
+
+    async def get_pool_price_ratios_async(
+        self,
+        pool_address: str,
+        start_timestamp: int,
+        end_timestamp: int,
+        interval: int,
+    ) -> List[Dict[str, Union[int, str]]]:
+        """
+        Awaitable variant of get_pool_price_ratios.
+        """
+        ...
+
+    def get_pool_candles(
+        self,
+        pool_address: str,
+        start_timestamp: int,
+        end_timestamp: int,
+        interval: int
+    ) -> List[Dict[str, Any]]:
+        """
+        Get OHLCV candles of a pool from its swaps. Candles are keyed like get_pool_price_ratios by the timestamp
+        closing their interval, the last one being the candle containing end_timestamp. Intervals without swaps
+        repeat the previous close.
+
+        Args:
+            pool_address (str): Pool address.
+            start_timestamp (int): Start timestamp.
+            end_timestamp (int): End timestamp.
+            interval (int): Candle interval in seconds.
+
+        Returns:
+            List: List of candles ordered by timestamp.
+            [
+                {
+                    "timestamp": int,
+                    "open": Optional[str],  # price of token0 in token1 after the first swap, adjusted for the token decimals
+                    "high": Optional[str],
+                    "low": Optional[str],
+                    "close": Optional[str],  # None for every price until the first known price
+                    "swap_count": int,
+                    "volume0": str,  # token0 swapped in either direction, in whole tokens
+                    "volume1": str,
+                    "vwap": Optional[str]  # volume1 per volume0, None without swaps
                 },
                 ...
             ]
+        Examples:
+        >>> uniswap_fetcher.get_pool_candles("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8", 1733875200, 1733961600, 3600)
+            get the hourly candles of the USDC/WETH 0.3% pool over a day.
+        """
+        ...
+
+    async def get_pool_candles_async(
+        self,
+        pool_address: str,
+        start_timestamp: int,
+        end_timestamp: int,
+        interval: int
+    ) -> List[Dict[str, Any]]:
+        """
+        Awaitable variant of get_pool_candles.
+        """
+        ...
+
+    def get_pool_state(
+        self,
+        pool_address: str,
+        block_number: Optional[int] = None
+    ) -> Dict:
+        """
+        Get the full state of a pool at a historical block. Every contract call is pinned to the block.
+
+        Args:
+            pool_address (str): Pool address.
+            block_number (Optional[int]): Block to read the state at. Defaults to the latest block.
+
+        Returns:
+            Dict: JSON object containing the pool state.
+            {
+                "pool_address": str,
+                "block_number": int,
+                "token0": str,
+                "token1": str,
+                "fee": int,
+                "tick_spacing": int,
+                "sqrt_price_x96": str,
+                "tick": int,
+                "observation_index": int,
+                "observation_cardinality": int,
+                "observation_cardinality_next": int,
+                "fee_protocol": int,
+                "unlocked": bool,
+                "liquidity": str,
+                "fee_growth_global0_x128": str,
+                "fee_growth_global1_x128": str,
+                "protocol_fees_token0": str,
+                "protocol_fees_token1": str
+            }
+        Examples:
+        >>> uniswap_fetcher.get_pool_state("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640", 12376933)
+            read the USDC/WETH 0.05% pool state at block 12376933.
+        """
+        ...
+
+    async def get_pool_state_async(
+        self,
+        pool_address: str,
+        block_number: Optional[int] = None
+    ) -> Dict:
+        """
+        Awaitable variant of get_pool_state.
+        """
+        ...
+
+    def get_tick_map(
+        self,
+        pool_address: str,
+        block_number: Optional[int] = None
+    ) -> Dict:
+        """
+        Get every initialized tick of a pool at a block. The tickBitmap words over the pool's tick spacing are
+        scanned and ticks(tick) is read for each set bit, batched through Multicall3 and pinned to the block.
+
+        Args:
+            pool_address (str): Pool address.
+            block_number (Optional[int]): Block to read the ticks at. Defaults to the latest block.
+
+        Returns:
+            Dict: JSON object containing the tick map.
+            {
+                "pool_address": str,
+                "block_number": int,
+                "tick_spacing": int,
+                "tick": int,  # current tick
+                "sqrt_price_x96": str,
+                "liquidity": str,  # active liquidity at the current tick
+                "ticks": [
+                    {
+                        "tick": int,
+                        "liquidity_net": str,
+                        "liquidity_gross": str,
+                        "liquidity": str  # active liquidity between this tick and the next one
+                    },
+                    ...
+                ]  # ordered by tick
+            }
+        Examples:
+        >>> uniswap_fetcher.get_tick_map("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640", 18000000)
+            read the initialized ticks of the USDC/WETH 0.05% pool at block 18000000.
+        """
+        ...
+
+    async def get_tick_map_async(
+        self,
+        pool_address: str,
+        block_number: Optional[int] = None
+    ) -> Dict:
+        """
+        Awaitable variant of get_tick_map.
+        """
+        ...
+
+    def replay_liquidity(
+        self,
+        pool_address: str,
+        to_block: int,
+        checkpoint: Optional[str] = None,
+        verify: bool = False
+    ) -> Dict:
+        """
+        Rebuild the per-tick liquidity of a pool by replaying its Mint and Burn events up to a block.
+        Only logs are read, so no archive node is needed unless verify is set.
+
+        Args:
+            pool_address (str): Pool address.
+            to_block (int): Last block to replay. Raises RuntimeError when it is past the chain head.
+            checkpoint (Optional[str]): "checkpoint" of a previous replay of the same pool to extend. Without it the
+                replay starts at the factory deployment block.
+            verify (bool): Compare the result with get_tick_map at to_block and report the ticks that differ.
+
+        Returns:
+            Dict: JSON object containing the replayed liquidity.
+            {
+                "pool_address": str,
+                "block_number": int,  # last replayed block
+                "ticks": [
+                    {
+                        "tick": int,
+                        "liquidity_net": str,
+                        "liquidity_gross": str,
+                        "liquidity": str  # active liquidity between this tick and the next one
+                    },
+                    ...
+                ],
+                "checkpoint": str,  # pass back to extend the replay
+                "mismatched_ticks": List[int]  # only with verify=True
+            }
+            A checkpoint is not safe against reorgs: events of blocks up to to_block that a reorg replaces are not
+            replayed again, so checkpoint only blocks that are final or some confirmations behind the head.
+        Examples:
+        >>> replay = uniswap_fetcher.replay_liquidity("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8", 12375933)
+        >>> uniswap_fetcher.replay_liquidity("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8", 12376933, replay["checkpoint"], verify=True)
+            replay the USDC/WETH 0.3% pool up to block 12375933, then extend it by 1000 blocks and check it against the chain.
+        """
+        ...
+
+    async def replay_liquidity_async(
+        self,
+        pool_address: str,
+        to_block: int,
+        checkpoint: Optional[str] = None,
+        verify: bool = False
+    ) -> Dict:
         """
+        Awaitable variant of replay_liquidity.
+        """
+        ...
+
+    def get_twap(
+        self,
+        pool_address: str,
+        window_seconds: int,
+        block_number: Optional[int] = None
+    ) -> Dict:
+        """
+        Get the time-weighted average price of a pool over the window ending at a block, from observe([window_seconds, 0]).
+        The pool's observation cardinality must cover the window, otherwise the call reverts with "OLD".
+
+        Args:
+            pool_address (str): Pool address.
+            window_seconds (int): Length of the averaging window in seconds.
+            block_number (Optional[int]): Block the window ends at. Defaults to the latest block.
+
+        Returns:
+            Dict: JSON object containing the TWAP.
+            {
+                "pool_address": str,
+                "block_number": int,
+                "window_seconds": int,
+                "arithmetic_mean_tick": int,  # rounded towards negative infinity
+                "harmonic_mean_liquidity": Optional[str],  # None when no liquidity was in range for the window
+                "price": str,  # price of token0 in token1, adjusted for the token decimals
+                "inverse_price": str,  # price of token1 in token0
+                "token0": str,
+                "token1": str
+            }
+        Examples:
+        >>> uniswap_fetcher.get_twap("0x8ad599c3a0ff1de082011efddc58f1908eb6e6d8", 1800, 17000000)
+            get the 30 minute TWAP of the USDC/WETH 0.3% pool at block 17000000.
+        """
+        ...
+
+    async def get_twap_async(
+        self,
+        pool_address: str,
+        window_seconds: int,
+        block_number: Optional[int] = None
+    ) -> Dict:
+        """
+        Awaitable variant of get_twap.
+        """
+        ...
+
+    def compute_pool_address(
+        self,
+        token0: str,
+        token1: str,
+        fee: int
+    ) -> str:
+        """
+        Compute a pool address offline from the factory address, the sorted token pair, the fee and the pool init code hash.
+
+        Args:
+            token0 (str): Address of one token of the pair.
+            token1 (str): Address of the other token of the pair. Order does not matter.
+            fee (int): Fee tier, e.g. 500, 3000 or 10000.
+
+        Returns:
+            str: The pool address.
+        Examples:
+        >>> uniswap_fetcher.compute_pool_address("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", 500)
+            "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"
+        """
+        ...
+
+    def get_v2_pair_events_by_token_pairs(
+        self,
+        token_pairs: List[Tuple[str, str]],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get Uniswap V2 pair events by token pairs. Pair addresses are derived offline with CREATE2
+        from the V2 factory of the fetcher's chain; only Ethereum (1) is supported, other chains raise ValueError.
+
+        Args:
+            token_pairs (List[Tuple[str, str]]): List of token pairs. Token order does not matter.
+            from_block (int): Starting block number.
+            to_block (int): Ending block number.
+            event_types (Optional[List[str]]): Event types to fetch out of "swap", "mint", "burn" and "sync".
+                Defaults to all of them. Raises ValueError for unknown event types.
+
+        Returns:
+            Dict: JSON object containing the pair events, in the same envelope as get_pool_events_by_token_pairs.
+            {
+                data: [
+                    {
+                        "event": {
+                            "type": str,
+                            "data": {
+                                V2SwapEvent, V2MintEvent, V2BurnEvent, V2SyncEvent
+                            }
+                        },
+                        "block_number": int,
+                        "transaction_hash": str,
+                        "pool_address": str,  # the pair address
+                        "timestamp": int
+                    },
+                    ...
+                ]
+                overall_hash: str
+                protocol: str  # "uniswap_v2"
+                pairs: [
+                    {
+                        "token0": str,
+                        "token1": str,
+                        "pair_address": Optional[str],
+                        "status": str  # "found" or "missing"
+                    },
+                    ...
+                ]
+            }
+        Examples:
+        >>> uniswap_fetcher.get_v2_pair_events_by_token_pairs([("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")], 10008355, 10008555)
+            fetch USDC/WETH pair events between the block numbers 10008355 and 10008555.
+        """
+        ...
+
+    async def get_v2_pair_events_by_token_pairs_async(
+        self,
+        token_pairs: List[Tuple[str, str]],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of get_v2_pair_events_by_token_pairs.
+        """
+        ...
+
+    def get_v2_pair_events_by_pair_addresses(
+        self,
+        pair_addresses: List[str],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get Uniswap V2 pair events by pair addresses.
+
+        Args:
+            pair_addresses (List[str]): List of pair addresses. Raises ValueError when empty or for an invalid address.
+            from_block (int): Starting block number.
+            to_block (int): Ending block number.
+            event_types (Optional[List[str]]): Event types to fetch out of "swap", "mint", "burn" and "sync".
+                Defaults to all of them.
+
+        Returns:
+            Dict: JSON object containing the pair events, see get_v2_pair_events_by_token_pairs (without "pairs").
+        Examples:
+        >>> uniswap_fetcher.get_v2_pair_events_by_pair_addresses(["0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc"], 10008355, 10008555, ["swap"])
+            fetch the swaps of the USDC/WETH pair between the block numbers 10008355 and 10008555.
+        """
+        ...
+
+    async def get_v2_pair_events_by_pair_addresses_async(
+        self,
+        pair_addresses: List[str],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of get_v2_pair_events_by_pair_addresses.
+        """
+        ...
+
+    def fetch_v2_pair_data(
+        self,
+        token_pairs: List[Tuple[str, str]],
+        start_timestamp: int,
+        end_timestamp: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get Uniswap V2 pair events by token pairs between two timestamps.
+
+        Args:
+            token_pairs (List[Tuple[str, str]]): List of token pairs.
+            start_timestamp (int): Starting timestamp.
+            end_timestamp (int): Ending timestamp.
+            event_types (Optional[List[str]]): Event types to fetch out of "swap", "mint", "burn" and "sync".
+
+        Returns:
+            Dict: JSON object containing the pair events, see get_v2_pair_events_by_token_pairs.
+        """
+        ...
+
+    async def fetch_v2_pair_data_async(
+        self,
+        token_pairs: List[Tuple[str, str]],
+        start_timestamp: int,
+        end_timestamp: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of fetch_v2_pair_data.
+        """
+        ...
+
+    def get_v2_pair_created_events_between_two_timestamps(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> Dict:
+        """
+        Get Uniswap V2 PairCreated events between two timestamps.
+
+        Args:
+            start_timestamp (int): Starting timestamp.
+            end_timestamp (int): Ending timestamp.
+
+        Returns:
+            Dict: JSON object containing the pair created events.
+            [
+                {
+                    "token0": {"address": str, "name": str, "symbol": str, "decimals": int},
+                    "token1": {"address": str, "name": str, "symbol": str, "decimals": int},
+                    "pair_address": str,
+                    "block_number": int,
+                    "protocol": str  # "uniswap_v2"
+                },
+                ...
+            ]
+        """
+        ...
+
+    async def get_v2_pair_created_events_between_two_timestamps_async(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> Dict:
+        """
+        Awaitable variant of get_v2_pair_created_events_between_two_timestamps.
+        """
+        ...
+
+    def compute_v2_pair_address(
+        self,
+        token0: str,
+        token1: str
+    ) -> str:
+        """
+        Compute a Uniswap V2 pair address offline from the V2 factory address, the sorted token pair and the pair init code hash.
+
+        Args:
+            token0 (str): Address of one token of the pair.
+            token1 (str): Address of the other token of the pair. Order does not matter.
+
+        Returns:
+            str: The pair address.
+        Examples:
+        >>> uniswap_fetcher.compute_v2_pair_address("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")
+            "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc"
+        """
+        ...
+
+    def get_v4_pool_events_by_pool_ids(
+        self,
+        pool_ids: List[str],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get Uniswap V4 PoolManager events by PoolId. V4 pools live inside a singleton PoolManager,
+        so events are matched on the PoolId in topic1 instead of a pool address.
+        Only Ethereum (1) is supported; other chains raise ValueError.
+
+        Args:
+            pool_ids (List[str]): List of PoolIds (bytes32 hex strings). Must not be empty.
+            from_block (int): Starting block number.
+            to_block (int): Ending block number.
+            event_types (Optional[List[str]]): Event types to fetch out of "initialize", "modify_liquidity",
+                "swap" and "donate". Defaults to all of them. Raises ValueError for unknown event types.
+
+        Returns:
+            Dict: JSON object containing the pool events, in the same envelope as get_pool_events_by_token_pairs.
+            {
+                data: [
+                    {
+                        "event": {
+                            "type": str,
+                            "data": {
+                                V4InitializeEvent, V4ModifyLiquidityEvent, V4SwapEvent, V4DonateEvent
+                            }  # "id" holds the PoolId
+                        },
+                        "block_number": int,
+                        "transaction_hash": str,
+                        "pool_address": str,  # the PoolManager address
+                        "timestamp": int
+                    },
+                    ...
+                ]
+                overall_hash: str
+                protocol: str  # "uniswap_v4"
+                pools: [
+                    {
+                        "pool_id": str,
+                        "pool_key": Optional[{
+                            "currency0": str,  # the zero address is native ETH
+                            "currency1": str,
+                            "fee": int,
+                            "tick_spacing": int,
+                            "hooks": str
+                        }],
+                        "initialize_block": Optional[int],
+                        "status": str  # "found", or "missing" when the pool was not initialized by to_block
+                    },
+                    ...
+                ]  # PoolKeys are cached by the fetcher, so only pools not found before are looked up
+            }
+        Examples:
+        >>> uniswap_fetcher.get_v4_pool_events_by_pool_ids(["0x21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27"], 21700000, 21700100, ["swap"])
+            fetch the swaps of the ETH/USDC 0.05% V4 pool between the block numbers 21700000 and 21700100.
+        """
+        ...
+
+    async def get_v4_pool_events_by_pool_ids_async(
+        self,
+        pool_ids: List[str],
+        from_block: int,
+        to_block: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of get_v4_pool_events_by_pool_ids.
+        """
+        ...
+
+    def fetch_v4_pool_data(
+        self,
+        pool_ids: List[str],
+        start_timestamp: int,
+        end_timestamp: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get Uniswap V4 PoolManager events by PoolId between two timestamps.
+
+        Args:
+            pool_ids (List[str]): List of PoolIds.
+            start_timestamp (int): Starting timestamp.
+            end_timestamp (int): Ending timestamp.
+            event_types (Optional[List[str]]): Event types to fetch out of "initialize", "modify_liquidity", "swap" and "donate".
+
+        Returns:
+            Dict: JSON object containing the pool events, see get_v4_pool_events_by_pool_ids.
+        """
+        ...
+
+    async def fetch_v4_pool_data_async(
+        self,
+        pool_ids: List[str],
+        start_timestamp: int,
+        end_timestamp: int,
+        event_types: Optional[List[str]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of fetch_v4_pool_data.
+        """
+        ...
+
+    def get_v4_pool_initialized_events_between_two_timestamps(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> Dict:
+        """
+        Get the Uniswap V4 pools initialized between two timestamps, with their PoolKey.
+
+        Args:
+            start_timestamp (int): Starting timestamp.
+            end_timestamp (int): Ending timestamp.
+
+        Returns:
+            Dict: JSON object containing the initialized pools.
+            [
+                {
+                    "pool_id": str,
+                    "currency0": str,
+                    "currency1": str,
+                    "fee": int,
+                    "tick_spacing": int,
+                    "hooks": str,
+                    "sqrt_price_x96": str,
+                    "tick": int,
+                    "block_number": int,
+                    "protocol": str  # "uniswap_v4"
+                },
+                ...
+            ]
+        """
+        ...
+
+    async def get_v4_pool_initialized_events_between_two_timestamps_async(
+        self,
+        start_timestamp: int,
+        end_timestamp: int
+    ) -> Dict:
+        """
+        Awaitable variant of get_v4_pool_initialized_events_between_two_timestamps.
+        """
+        ...
+
+    def compute_v4_pool_id(
+        self,
+        currency0: str,
+        currency1: str,
+        fee: int,
+        tick_spacing: int,
+        hooks: str = "0x0000000000000000000000000000000000000000"
+    ) -> str:
+        """
+        Compute a Uniswap V4 PoolId offline as keccak256 of the ABI encoded PoolKey.
+
+        Args:
+            currency0 (str): Address of one currency of the pool, the zero address for native ETH.
+            currency1 (str): Address of the other currency. Order does not matter.
+            fee (int): Pool fee in hundredths of a bip.
+            tick_spacing (int): Tick spacing of the pool.
+            hooks (str): Hooks contract of the pool. Defaults to no hooks.
+
+        Returns:
+            str: The PoolId.
+        Examples:
+        >>> uniswap_fetcher.compute_v4_pool_id("0x0000000000000000000000000000000000000000", "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", 500, 10)
+            "0x21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27"
+        """
+        ...
+
+    def tick_to_sqrt_price_x96(self, tick: int) -> str:
+        """
+        Compute the Q64.96 square root price of a tick offline, exactly as TickMath.getSqrtRatioAtTick does on chain.
+
+        Args:
+            tick (int): Tick between -887272 and 887272.
+
+        Returns:
+            str: The sqrtPriceX96 as a decimal string.
+        Examples:
+        >>> uniswap_fetcher.tick_to_sqrt_price_x96(0)
+            "79228162514264337593543950336"
+        """
+        ...
+
+    def sqrt_price_x96_to_tick(self, sqrt_price_x96: str) -> int:
+        """
+        Compute the greatest tick whose square root price is at most sqrt_price_x96, like TickMath.getTickAtSqrtRatio.
+
+        Args:
+            sqrt_price_x96 (str): Q64.96 square root price as a decimal string.
+
+        Returns:
+            int: The tick.
+        Examples:
+        >>> uniswap_fetcher.sqrt_price_x96_to_tick("1744244129640337381386292603617838")
+            200000
+        """
+        ...
+
+    def sqrt_price_x96_to_price(
+        self,
+        sqrt_price_x96: str,
+        token0_decimals: int = 0,
+        token1_decimals: int = 0
+    ) -> Tuple[str, str]:
+        """
+        Convert a Q64.96 square root price into exact decimal prices in both orientations.
+        Prices with a finite decimal expansion are exact, others are truncated to 40 significant digits.
+
+        Args:
+            sqrt_price_x96 (str): Q64.96 square root price as a decimal string.
+            token0_decimals (int): Decimals of token0. Defaults to 0, giving the raw price between base units.
+            token1_decimals (int): Decimals of token1. Defaults to 0.
+
+        Returns:
+            Tuple[str, str]: Price of token0 in token1 and price of token1 in token0.
+        Examples:
+        >>> uniswap_fetcher.sqrt_price_x96_to_price("1744244129640337381386292603617838", 6, 18)
+            ("0.000484680...", "2063.215...")
+        """
+        ...
+
+    def get_position_events(
+        self,
+        from_block: int,
+        to_block: int,
+        token_ids: Optional[List[int]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get NonfungiblePositionManager events and map every position NFT to its pool, tick range and owner.
+        Pool Mint and Burn events record the position manager as owner, so they are joined with the manager's
+        IncreaseLiquidity and DecreaseLiquidity events by transaction hash. Positions minted or last transferred
+        before from_block are completed with the manager's positions() and ownerOf() at to_block.
+
+        Args:
+            from_block (int): Starting block number.
+            to_block (int): Ending block number.
+            token_ids (Optional[List[int]]): Position NFTs to fetch. Defaults to every position touched in the range.
+
+        Returns:
+            Dict: JSON object containing the position manager events, in the same envelope as get_pool_events_by_token_pairs.
+            {
+                data: [
+                    {
+                        "event": {
+                            "type": str,  # "increase_liquidity", "decrease_liquidity", "collect" or "transfer"
+                            "data": {
+                                LiquidityChange, PositionCollect, PositionTransfer
+                            }
+                        },
+                        "block_number": int,
+                        "transaction_hash": str,
+                        "pool_address": str,  # the position manager address
+                        "timestamp": int
+                    },
+                    ...
+                ]
+                overall_hash: str
+                protocol: str
+                positions: [
+                    {
+                        "token_id": str,
+                        "owner": Optional[str],  # None once the NFT is burned
+                        "pool_address": Optional[str],
+                        "tick_lower": Optional[int],
+                        "tick_upper": Optional[int],
+                        "liquidity_added": str,
+                        "liquidity_removed": str,
+                        "burned": bool
+                    },
+                    ...
+                ]
+            }
+        Examples:
+        >>> uniswap_fetcher.get_position_events(12369621, 12370621, [1])
+            fetch the events of position NFT 1 and resolve its pool, tick range and owner.
+        """
+        ...
+
+    async def get_position_events_async(
+        self,
+        from_block: int,
+        to_block: int,
+        token_ids: Optional[List[int]] = None
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of get_position_events.
+        """
+        ...
+
+    def get_events(
+        self,
+        addresses: List[str],
+        abi_json: str,
+        event_names: Optional[List[str]],
+        from_block: int,
+        to_block: int
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Get the events of arbitrary contracts, decoded with a user supplied ABI.
+
+        Args:
+            addresses (List[str]): Contract addresses to fetch events from. Must not be empty.
+            abi_json (str): ABI JSON array, like the files in src/contracts/.
+            event_names (Optional[List[str]]): Events to fetch, every overload included. None fetches every
+                non-anonymous event of the ABI. Raises ValueError for unknown or anonymous events.
+            from_block (int): Starting block number.
+            to_block (int): Ending block number.
+
+        Returns:
+            Dict: JSON object containing the events, in the same envelope as get_pool_events_by_token_pairs.
+            {
+                data: [
+                    {
+                        "event": {
+                            "type": str,  # the event name
+                            "data": Dict[str, Any]  # parameter name -> value; unnamed parameters are keyed by position
+                        },
+                        "block_number": int,
+                        "transaction_hash": str,
+                        "pool_address": str,  # the emitting contract
+                        "timestamp": int
+                    },
+                    ...
+                ]
+                overall_hash: str
+            }
+            Addresses and integers are hex strings, bytes are 0x-prefixed hex, arrays and tuples are lists.
+        Examples:
+        >>> uniswap_fetcher.get_events(["0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"], open("erc20_abi.json").read(), ["Transfer"], 12376933, 12376943)
+            fetch the USDC transfers between the block numbers 12376933 and 12376943.
+        """
+        ...
+
+    async def get_events_async(
+        self,
+        addresses: List[str],
+        abi_json: str,
+        event_names: Optional[List[str]],
+        from_block: int,
+        to_block: int
+    ) -> Dict[str, Dict[str, Union[str, int, Dict]]]:
+        """
+        Awaitable variant of get_events.
+        """
+        ...
+
+class PoolEventStream:
+    """
+    Iterator returned by UniswapFetcher.iter_pool_events_by_pool_addresses. Each step fetches and decodes the
+    next window of the range. A window that fails raises RuntimeError and is fetched again by the next step.
+    """
+    def __iter__(self) -> "PoolEventStream":
+        ...
+
+    def __next__(self) -> Dict[str, Any]:
         ...
-    
\ No newline at end of file